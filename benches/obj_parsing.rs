@@ -0,0 +1,76 @@
+use std::io::Write;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use agr::model::{LoadOptions, MeshData};
+
+// the bundled models are small, so they are repeated with their indices shifted to the copy
+// they belong to, giving one large file made of separate meshes
+fn scaled(source: &str, copies: usize) -> String {
+
+    let count = |keyword: &str| source.lines().filter(|line| line.split_whitespace().next() == Some(keyword)).count();
+    let counts = [count("v"), count("vt"), count("vn")];
+
+    let mut scaled = String::with_capacity(source.len() * copies);
+    for copy in 0..copies {
+        for line in source.lines() {
+            match line.split_whitespace().next() {
+                Some("f") | Some("l") | Some("p") => {
+                    let mut tokens = line.split_whitespace();
+                    scaled.push_str(tokens.next().unwrap());
+                    for token in tokens {
+                        let parts = token.split('/').enumerate().map(|(i, part)| match part.parse::<usize>() {
+                            Ok(index) => (index + copy * counts[i]).to_string(),
+                            Err(_) => part.to_string()
+                        });
+                        scaled.push(' ');
+                        scaled.push_str(&parts.collect::<Vec<_>>().join("/"));
+                    }
+                }
+                _ => scaled.push_str(line)
+            }
+            scaled.push('\n');
+        }
+    }
+    scaled
+}
+
+fn parse_bytes(c: &mut Criterion) {
+
+    let options = LoadOptions::default();
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(10);
+    for (name, source, copies) in [("teapot", include_str!("../src/teapot.obj"), 200), ("cow", include_str!("../src/cow.obj"), 50)] {
+        let source = scaled(source, copies);
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::new(name, copies), &source, |b, source| {
+            b.iter(|| MeshData::from_obj_bytes(source.as_bytes(), name, &options, &|_| ()).unwrap())
+        });
+        // the same file on a single thread, to see what the parallel passes gain
+        let single = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        group.bench_with_input(BenchmarkId::new(format!("{} single thread", name), copies), &source, |b, source| {
+            b.iter(|| single.install(|| MeshData::from_obj_bytes(source.as_bytes(), name, &options, &|_| ()).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+fn parse_file(c: &mut Criterion) {
+
+    let source = scaled(include_str!("../src/cow.obj"), 50);
+    let path = std::env::temp_dir().join("agr_bench_cow.obj");
+    std::fs::File::create(&path).unwrap().write_all(source.as_bytes()).unwrap();
+    let filename = path.to_string_lossy().to_string();
+
+    let options = LoadOptions::default();
+    let mut group = c.benchmark_group("parse_file");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.bench_function("cow 50 mapped", |b| b.iter(|| MeshData::from_obj_file(&filename, &options).unwrap()));
+    group.finish();
+
+    std::fs::remove_file(&path).ok();
+}
+
+criterion_group!(benches, parse_bytes, parse_file);
+criterion_main!(benches);
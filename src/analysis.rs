@@ -0,0 +1,287 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::model::MeshData;
+
+// what a mesh is made of and how its triangles connect. edges and components are found on
+// positions, so seams where only normals or texture coordinates differ don't split the surface
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MeshReport {
+    pub vertices: usize,
+    pub triangles: usize,
+    pub submeshes: usize,
+    pub materials: usize,
+    pub line_segments: usize,
+    pub points: usize,
+    // vertices equal in every attribute to an earlier one, welding leaves none
+    pub duplicate_vertices: usize,
+    // vertices at the position of an earlier one with some other attribute differing
+    pub shared_positions: usize,
+    // triangles using the same vertex twice
+    pub degenerate_triangles: usize,
+    // triangles with three distinct vertices whose corners are collinear or coincide
+    pub zero_area_triangles: usize,
+    pub edges: usize,
+    // edges of a single triangle
+    pub boundary_edges: usize,
+    // edges shared by more than two triangles
+    pub non_manifold_edges: usize,
+    // holes in the surface, each a chain of boundary edges
+    pub boundary_loops: usize,
+    // groups of triangles connected through their corners
+    pub connected_components: usize,
+    // every edge has exactly two triangles, so the surface encloses a volume
+    pub watertight: bool
+}
+
+// disjoint sets with path halving
+struct Sets {
+    parents: Vec<usize>
+}
+
+impl Sets {
+
+    fn new(len: usize) -> Self {
+        Self { parents: (0..len).collect() }
+    }
+
+    fn find(&mut self, mut set: usize) -> usize {
+        while self.parents[set] != set {
+            self.parents[set] = self.parents[self.parents[set]];
+            set = self.parents[set];
+        }
+        set
+    }
+
+    fn join(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parents[a] = b;
+    }
+
+    // the number of sets holding at least one of `members`
+    fn count<I: IntoIterator<Item = usize>>(&mut self, members: I) -> usize {
+        let mut roots = members.into_iter().map(|member| self.find(member)).collect::<Vec<_>>();
+        roots.sort_unstable();
+        roots.dedup();
+        roots.len()
+    }
+}
+
+fn bits<const N: usize>(values: [f32; N]) -> [u32; N] {
+    // adding zero turns -0.0 into 0.0, so both compare the same
+    values.map(|value| (value + 0.0).to_bits())
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn length2(v: [f32; 3]) -> f32 {
+    v[0] * v[0] + v[1] * v[1] + v[2] * v[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+// compares twice the area to the longest edge, so the answer doesn't depend on the scale of the mesh
+fn has_zero_area(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> bool {
+
+    let (ab, ac, bc) = (sub(b, a), sub(c, a), sub(c, b));
+    let longest = length2(ab).max(length2(ac)).max(length2(bc));
+    length2(cross(ab, ac)).sqrt() <= longest * f32::EPSILON
+}
+
+pub fn analyze(mesh: &MeshData) -> MeshReport {
+
+    let mut report = MeshReport {
+        vertices: mesh.positions.len(),
+        triangles: mesh.indices.len() / 3,
+        submeshes: mesh.submeshes.len(),
+        materials: mesh.materials.len(),
+        line_segments: mesh.line_indices.len() / 2,
+        points: mesh.point_indices.len(),
+        ..MeshReport::default()
+    };
+
+    // every vertex gets the index of the first vertex at its position
+    let mut first_at = HashMap::new();
+    let mut seen = HashSet::new();
+    let position_ids = mesh.positions.iter().enumerate().map(|(i, position)| {
+        let vertex = (
+            bits(*position),
+            mesh.normals.get(i).copied().map(bits),
+            mesh.texcoords.get(i).copied().map(bits),
+            mesh.colors.get(i).copied().map(bits),
+            mesh.tangents.get(i).copied().map(bits)
+        );
+        let id = *first_at.entry(vertex.0).or_insert(i);
+        if !seen.insert(vertex) {
+            report.duplicate_vertices += 1;
+        } else if id != i {
+            report.shared_positions += 1;
+        }
+        id
+    }).collect::<Vec<usize>>();
+
+    let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut surface = Sets::new(mesh.positions.len());
+    let mut used = Vec::new();
+    for triangle in mesh.indices.chunks_exact(3) {
+        if triangle[0] == triangle[1] || triangle[1] == triangle[2] || triangle[0] == triangle[2] {
+            report.degenerate_triangles += 1;
+            continue;
+        }
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| position_ids[i as usize]);
+        if has_zero_area(mesh.positions[a], mesh.positions[b], mesh.positions[c]) {
+            report.zero_area_triangles += 1;
+        }
+        // corners at the same position make no edge between them
+        if a == b || b == c || a == c {
+            continue;
+        }
+        for (from, to) in [(a, b), (b, c), (c, a)] {
+            *edges.entry((from.min(to), from.max(to))).or_insert(0) += 1;
+        }
+        surface.join(a, b);
+        surface.join(a, c);
+        used.push(a);
+    }
+    report.edges = edges.len();
+    report.connected_components = surface.count(used);
+
+    let mut boundaries = Sets::new(mesh.positions.len());
+    let mut boundary_vertices = Vec::new();
+    for (&(a, b), &triangles) in &edges {
+        match triangles {
+            1 => {
+                report.boundary_edges += 1;
+                boundaries.join(a, b);
+                boundary_vertices.push(a);
+            }
+            2 => (),
+            _ => report.non_manifold_edges += 1
+        }
+    }
+    report.boundary_loops = boundaries.count(boundary_vertices);
+    report.watertight = report.triangles > 0 && report.boundary_edges == 0 && report.non_manifold_edges == 0;
+    report
+}
+
+impl MeshReport {
+
+    // (name, value) of every count, in the order they are shown
+    fn counts(&self) -> [(&'static str, usize); 15] {
+        [
+            ("vertices", self.vertices),
+            ("triangles", self.triangles),
+            ("submeshes", self.submeshes),
+            ("materials", self.materials),
+            ("line_segments", self.line_segments),
+            ("points", self.points),
+            ("duplicate_vertices", self.duplicate_vertices),
+            ("shared_positions", self.shared_positions),
+            ("degenerate_triangles", self.degenerate_triangles),
+            ("zero_area_triangles", self.zero_area_triangles),
+            ("edges", self.edges),
+            ("boundary_edges", self.boundary_edges),
+            ("non_manifold_edges", self.non_manifold_edges),
+            ("boundary_loops", self.boundary_loops),
+            ("connected_components", self.connected_components)
+        ]
+    }
+
+    // one flat object, field names as in the struct
+    pub fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+
+        write!(writer, "{{")?;
+        for (name, value) in self.counts() {
+            write!(writer, "\"{}\": {}, ", name, value)?;
+        }
+        writeln!(writer, "\"watertight\": {}}}", self.watertight)
+    }
+}
+
+impl fmt::Display for MeshReport {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+
+        for (name, value) in self.counts() {
+            writeln!(f, "{:<22}{}", name.replace('_', " "), value)?;
+        }
+        writeln!(f, "{:<22}{}", "watertight", if self.watertight { "yes" } else { "no" })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{LoadOptions, ParseMode};
+
+    fn analyze_source(source: &str, options: &LoadOptions) -> MeshReport {
+        let (mesh, _) = MeshData::from_obj(source.as_bytes(), "test.obj", options).unwrap();
+        analyze(&mesh)
+    }
+
+    const CUBE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0 0 1\nv 1 0 1\nv 1 1 1\nv 0 1 1\n\
+        f 1 4 3 2\nf 5 6 7 8\nf 1 2 6 5\nf 2 3 7 6\nf 3 4 8 7\nf 4 1 5 8\n";
+
+    #[test]
+    fn closed_cube_is_watertight() {
+
+        // the 60 degree crease angle gives every face its own vertices, which the edges see through
+        let report = analyze_source(CUBE, &LoadOptions::default());
+        assert_eq!(report.triangles, 12);
+        assert_eq!(report.vertices, 24);
+        assert_eq!(report.shared_positions, 16);
+        assert_eq!(report.edges, 18);
+        assert_eq!((report.boundary_edges, report.boundary_loops, report.non_manifold_edges), (0, 0, 0));
+        assert_eq!(report.connected_components, 1);
+        assert!(report.watertight);
+    }
+
+    #[test]
+    fn open_and_non_manifold_meshes_are_found() {
+
+        // a cube without its top, and a separate fan of three triangles around one edge
+        let source = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0 0 1\nv 1 0 1\nv 1 1 1\nv 0 1 1\n\
+            f 1 4 3 2\nf 1 2 6 5\nf 2 3 7 6\nf 3 4 8 7\nf 4 1 5 8\n\
+            v 5 0 0\nv 5 1 0\nv 6 0 0\nv 4 0 0\nv 5 0 1\n\
+            f 9 10 11\nf 9 10 12\nf 9 10 13\n";
+        let report = analyze_source(source, &LoadOptions::default());
+        assert_eq!(report.boundary_loops, 2);
+        assert_eq!(report.non_manifold_edges, 1);
+        assert_eq!(report.connected_components, 2);
+        assert!(!report.watertight);
+    }
+
+    #[test]
+    fn broken_triangles_and_duplicates_are_counted() {
+
+        let options = LoadOptions { mode: ParseMode::Strict, weld_epsilon: None, ..LoadOptions::default() };
+        // a triangle repeating a vertex, one with collinear corners and a copy of the first vertex
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 2 0 0\nv 0 0 0\nvn 0 0 1\n\
+            f 1//1 2//1 3//1\nf 1//1 2//1 1//1\nf 1//1 2//1 4//1\nf 5//1 2//1 3//1\n";
+        let report = analyze_source(source, &options);
+        assert_eq!(report.degenerate_triangles, 1);
+        assert_eq!(report.zero_area_triangles, 1);
+        assert_eq!(report.duplicate_vertices, 1);
+        let mut json = Vec::new();
+        report.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with("{\"vertices\": ") && json.contains("\"watertight\": false"), "{}", json);
+    }
+
+    #[test]
+    fn meshes_without_normals_are_analysed() {
+
+        let mesh = MeshData {
+            positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+            indices: vec![0, 1, 2, 0, 1, 3],
+            ..MeshData::default()
+        };
+        let report = analyze(&mesh);
+        assert_eq!((report.vertices, report.triangles, report.duplicate_vertices), (4, 2, 1));
+    }
+}
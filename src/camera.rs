@@ -3,6 +3,8 @@ use wgpu::util::DeviceExt;
 use winit::event::{DeviceEvent, ElementState, KeyboardInput, VirtualKeyCode};
 use std::f32::consts::FRAC_PI_2;
 
+use crate::model::BoundingSphere;
+
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
@@ -28,16 +30,16 @@ impl CameraData {
         }
     }
 
+    fn direction(&self) -> cgmath::Vector3<f32> {
+        cgmath::Vector3::new(
+            self.yaw.0.cos(),
+            self.pitch.0.sin(),
+            self.yaw.0.sin(),
+        ).normalize()
+    }
+
     fn calc_matrix(&self) -> cgmath::Matrix4<f32> {
-        cgmath::Matrix4::look_to_rh(
-            self.position,
-            cgmath::Vector3::new(
-                self.yaw.0.cos(),
-                self.pitch.0.sin(),
-                self.yaw.0.sin(),
-            ).normalize(),
-            cgmath::Vector3::unit_y()
-        )
+        cgmath::Matrix4::look_to_rh(self.position, self.direction(), cgmath::Vector3::unit_y())
     }
 }
 
@@ -64,6 +66,21 @@ impl Projection {
         self.aspect = width as f32 / height as f32;
     }
 
+    // distance from the center of a sphere at which it just fits in the narrower field of view
+    fn framing_distance(&self, radius: f32) -> f32 {
+
+        let half_fovy = self.fovy.0 * 0.5;
+        let half_fovx = (half_fovy.tan() * self.aspect).atan();
+        radius / half_fovy.min(half_fovx).sin()
+    }
+
+    // clip planes around a sphere seen from `distance`, leaving room to move towards it and away
+    // from it. the near plane is kept well away from zero, as depth precision depends on it
+    fn fit_planes(&mut self, radius: f32, distance: f32) {
+        self.znear = radius * 0.01;
+        self.zfar = (distance + radius) * 4.0;
+    }
+
     fn calc_matrix(&self) -> cgmath::Matrix4<f32> {
         OPENGL_TO_WGPU_MATRIX * cgmath::perspective(self.fovy, self.aspect, self.znear, self.zfar)
     }
@@ -288,6 +305,26 @@ impl Camera {
         &self.bind_group
     }
 
+    pub fn get_position(&self) -> cgmath::Point3<f32> {
+        self.data.position
+    }
+
+    // moves the camera back along its view direction until the sphere fills the view, then fits
+    // the clip planes and the movement speed to its size. an empty sphere leaves the camera as is
+    pub fn frame(&mut self, sphere: &BoundingSphere) {
+
+        if sphere.is_empty() {
+            return;
+        }
+        // a single point still gets a view around it
+        let radius = sphere.radius.max(1e-3);
+        let distance = self.projection.framing_distance(radius);
+        self.data.position = cgmath::Point3::from(sphere.center) - self.data.direction() * distance;
+        self.projection.fit_planes(radius, distance);
+        self.controller.speed = radius;
+        self.uniform.update_view_proj(&self.data, &self.projection);
+    }
+
     pub fn resize_projection(&mut self, new_size: &winit::dpi::PhysicalSize<u32>) {
         self.projection.resize(new_size.width, new_size.height);
     }
@@ -302,7 +339,7 @@ impl Camera {
                 }
             ) => self.controller.process_keyboard(*key, *state),
             DeviceEvent::MouseWheel { delta, .. } => {
-                self.controller.process_scroll(&delta);
+                self.controller.process_scroll(delta);
                 true
             }
             DeviceEvent::Button {
@@ -343,3 +380,22 @@ impl Camera {
         encoder.copy_buffer_to_buffer(&staging_buffer, 0, &self.buffer, 0, std::mem::size_of::<CameraUniform>() as wgpu::BufferAddress);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn framed_sphere_touches_the_narrower_side_of_the_view() {
+
+        // wider than tall, so the vertical field of view limits
+        let projection = Projection::new(800, 400, cgmath::Deg(60.0), 0.1, 100.0);
+        let distance = projection.framing_distance(2.0);
+        assert!((distance - 4.0).abs() < 1e-5);
+
+        // taller than wide, so the horizontal one does
+        let projection = Projection::new(400, 800, cgmath::Deg(60.0), 0.1, 100.0);
+        let half_fovx = ((30f32).to_radians().tan() * 0.5).atan();
+        assert!((projection.framing_distance(2.0) * half_fovx.sin() - 2.0).abs() < 1e-5);
+    }
+}
@@ -1,27 +1,35 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
 use cgmath::InnerSpace;
 use cgmath::Rotation3;
 use cgmath::Zero;
 use wgpu::util::DeviceExt;
 use winit::window::Window;
-use winit::event::DeviceEvent;
+use winit::event::{DeviceEvent, ElementState, KeyboardInput, VirtualKeyCode};
 
 use crate::camera;
 use crate::light;
+use crate::material;
 use crate::model;
 use crate::model::Model;
 use crate::model::Mesh;
 use crate::instance;
 use crate::texture;
 
+// model shown by the viewer when no other is given
+pub const MODEL_FILE: &str = "teapot.obj";
+
 const NUM_INSTANCES_PER_ROW: u32 = 10;
-const NUM_INSTANCES: u32 = NUM_INSTANCES_PER_ROW * NUM_INSTANCES_PER_ROW;
-const INSTANCE_DISPLACEMENT: cgmath::Vector3<f32> = cgmath::Vector3::new(NUM_INSTANCES_PER_ROW as f32 * 0.5, 0.0, NUM_INSTANCES_PER_ROW as f32 * 0.5);
+// distance between neighbouring instances, in diameters of the model
+const INSTANCE_SPACING: f32 = 1.25;
 
 pub struct Engine {
 
     // create surface and adapter
+    #[allow(dead_code)]
     instance: wgpu::Instance,
     // open connection to GPU, creating device
+    #[allow(dead_code)]
     adapter: wgpu::Adapter,
     // used to interact with the GPU
     device: wgpu::Device,
@@ -33,6 +41,11 @@ pub struct Engine {
     queue: wgpu::Queue,
     // render pipeline being used
     render_pipeline: wgpu::RenderPipeline,
+    // draws the wireframe of the selected submesh on top of everything
+    highlight_pipeline: wgpu::RenderPipeline,
+    // draw the `l` and `p` elements of the models
+    line_pipeline: wgpu::RenderPipeline,
+    point_pipeline: wgpu::RenderPipeline,
     // screen size
     window_size: winit::dpi::PhysicalSize<u32>,
     // camera
@@ -41,13 +54,19 @@ pub struct Engine {
     light: light::Light,
     // model
     models: Vec<model::SimpleFileModel>,
-    instance_buffer: wgpu::Buffer,
+    // (model, submesh) picked with tab
+    selected: Option<(usize, usize)>,
+    // models with vertex colours are shaded with them instead of their material colour
+    vertex_colors: bool,
+    // where each model is drawn, with one instance buffer for each model
+    instances: Vec<Vec<instance::Instance>>,
+    instance_buffers: Vec<wgpu::Buffer>,
     depth_texture: texture::Texture
 }
 
 impl Engine {
 
-    pub async fn new(window: &Window) -> Self {
+    pub async fn new(window: &Window, filename: &str, load_options: &model::LoadOptions) -> Result<Self, model::ObjError> {
 
         let window_size = window.inner_size();
         let instance = Engine::create_instance();
@@ -65,39 +84,61 @@ impl Engine {
         let light_data = light::LightData::new((2.0, 2.0, 2.0), (1.0, 1.0, 1.0));
         let (light, light_bind_group_layout) = light::Light::new(&device, light_data);
 
-        let bind_group_layouts = [&camera_bind_group_layout, &light_bind_group_layout];
+        let material_bind_group_layout = material::Material::create_bind_group_layout(&device);
 
-        let render_pipeline = Engine::create_render_pipeline(&device, &surface_config, &bind_group_layouts);
-        let models = vec![model::SimpleFileModel::new(&device, "teapot.obj").unwrap()];
+        let bind_group_layouts = [&camera_bind_group_layout, &light_bind_group_layout, &material_bind_group_layout];
 
-        let scale = 0.05;
-        let instances = (0..NUM_INSTANCES_PER_ROW).flat_map(|z| {
-            (0..NUM_INSTANCES_PER_ROW).map(move |x| {
-                let position = cgmath::Vector3 { x: x as f32 * 10.0, y: 0.0, z: z as f32 * 10.0 } - INSTANCE_DISPLACEMENT;
-
-                let rotation = if position.is_zero() {
-                    // this is needed so an object at (0, 0, 0) won't get scaled to zero
-                    // as Quaternions can effect scale if they're not created correctly
-                    cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), cgmath::Deg(0.0))
-                } else {
-                    cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
-                };
+        let render_pipeline = Engine::create_render_pipeline(&device, &surface_config, &bind_group_layouts, "fs_main", wgpu::PrimitiveTopology::TriangleList, wgpu::PolygonMode::Fill);
+        // unlit, so they don't use the material bind group. the highlight is drawn without one
+        // bound when its submesh is hidden
+        let highlight_pipeline = Engine::create_render_pipeline(&device, &surface_config, &bind_group_layouts[..2], "fs_highlight", wgpu::PrimitiveTopology::TriangleList, wgpu::PolygonMode::Line);
+        let line_pipeline = Engine::create_render_pipeline(&device, &surface_config, &bind_group_layouts[..2], "fs_unlit", wgpu::PrimitiveTopology::LineList, wgpu::PolygonMode::Fill);
+        let point_pipeline = Engine::create_render_pipeline(&device, &surface_config, &bind_group_layouts[..2], "fs_unlit", wgpu::PrimitiveTopology::PointList, wgpu::PolygonMode::Fill);
+        // parsing reports its progress from several threads, every tenth of it is logged once
+        let logged = AtomicU32::new(0);
+        let progress = |fraction: f32| {
+            let tenths = (fraction * 10.0) as u32;
+            if logged.fetch_max(tenths, Ordering::Relaxed) < tenths {
+                log::info!("loading {}: {}%", filename, tenths * 10);
+            }
+        };
+        let (scene, report) = model::load_scene(filename, load_options, &progress)?;
+        for warning in &report.warnings {
+            log::warn!("{}", warning);
+        }
+        if report.from_cache {
+            log::info!("loaded {} from the mesh cache", filename);
+        }
+        log::info!("welding merged {} vertices and dropped {} unreferenced ones", report.weld.merged, report.weld.unreferenced);
 
-                instance::Instance {
-                    position, rotation, scaling: cgmath::Vector3::new(scale, scale, scale)
+        // mirrored instances can't be drawn through the instance buffer, so such a scene is
+        // drawn as the one mesh they are baked into
+        let scene = if scene.has_mirrored_instances() {
+            model::SceneData { meshes: vec![scene.flatten()], instances: Some(vec![vec![instance::Instance::identity()]]) }
+        } else {
+            scene
+        };
+        // meshes the scene never draws aren't uploaded
+        let instances = match scene.instances {
+            Some(instances) => instances,
+            None => scene.meshes.iter().map(|mesh| Engine::create_grid(&mesh.bounding_sphere())).collect()
+        };
+        let (models, instances): (Vec<_>, Vec<_>) = scene.meshes.iter().zip(instances)
+            .filter(|(_, instances)| !instances.is_empty())
+            .map(|(mesh, instances)| (model::SimpleFileModel::from_mesh(&device, &queue, &material_bind_group_layout, mesh), instances))
+            .unzip();
+        let instance_buffers = instances.iter().map(|instances| {
+            let instance_data = instances.iter().map(instance::Instance::to_raw).collect::<Vec<_>>();
+            device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("Instance Buffer"),
+                    contents: bytemuck::cast_slice(&instance_data),
+                    usage: wgpu::BufferUsages::VERTEX,
                 }
-            })
-        }).collect::<Vec<_>>();
-        let instance_data = instances.iter().map(instance::Instance::to_raw).collect::<Vec<_>>();
-        let instance_buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Instance Buffer"),
-                contents: bytemuck::cast_slice(&instance_data),
-                usage: wgpu::BufferUsages::VERTEX,
-            }
-        );
+            )
+        }).collect();
         let depth_texture = texture::Texture::create_depth_texture(&device, &surface_config, "depth_texture");
-        Self {
+        let mut engine = Self {
             instance,
             adapter,
             device,
@@ -105,13 +146,52 @@ impl Engine {
             surface_config,
             queue,
             render_pipeline,
+            highlight_pipeline,
+            line_pipeline,
+            point_pipeline,
             window_size,
             camera,
             light,
             models,
-            instance_buffer,
+            selected: None,
+            vertex_colors: true,
+            instances,
+            instance_buffers,
             depth_texture
+        };
+        // the light sits above the scene, at the side of the camera, whatever the size of the model
+        let (_, sphere) = engine.get_scene_bounds();
+        if !sphere.is_empty() {
+            let center = cgmath::Point3::from(sphere.center);
+            engine.light.set_position(center + cgmath::Vector3::new(0.0, 1.0, 1.0) * sphere.radius);
         }
+        engine.frame_all();
+        Ok(engine)
+    }
+
+    // a grid of turned copies for models without a scene to place them. it is laid out from the
+    // size of the model, so models of any scale are shown the same
+    fn create_grid(sphere: &model::BoundingSphere) -> Vec<instance::Instance> {
+
+        let spacing = if sphere.radius > 0.0 { sphere.radius * 2.0 * INSTANCE_SPACING } else { 1.0 };
+        let offset = (NUM_INSTANCES_PER_ROW - 1) as f32 * 0.5;
+        (0..NUM_INSTANCES_PER_ROW).flat_map(|z| {
+            (0..NUM_INSTANCES_PER_ROW).map(move |x| {
+                let position = cgmath::Vector3 { x: x as f32 - offset, y: 0.0, z: z as f32 - offset } * spacing;
+
+                let rotation = if position.is_zero() {
+                    // this is needed so an object at (0, 0, 0) won't get scaled to zero
+                    // as Quaternions can effect scale if they're not created correctly
+                    cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), cgmath::Deg(0.0))
+                } else {
+                    cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
+                };
+
+                instance::Instance {
+                    position, rotation, scaling: cgmath::Vector3::new(1.0, 1.0, 1.0)
+                }
+            })
+        }).collect()
     }
 
     fn create_instance() -> wgpu::Instance {
@@ -142,13 +222,13 @@ impl Engine {
     fn create_surface_config(adapter: &wgpu::Adapter, surface: &wgpu::Surface, window_size: &winit::dpi::PhysicalSize<u32>) -> wgpu::SurfaceConfiguration {
         wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_preferred_format(&adapter).unwrap(),
+            format: surface.get_preferred_format(adapter).unwrap(),
             width: window_size.width,
             height: window_size.height,
             present_mode: wgpu::PresentMode::Fifo
         }
     }
-    fn create_render_pipeline(device: &wgpu::Device, surface_config: &wgpu::SurfaceConfiguration, bind_group_layouts: &[&wgpu::BindGroupLayout]) -> wgpu::RenderPipeline {
+    fn create_render_pipeline(device: &wgpu::Device, surface_config: &wgpu::SurfaceConfiguration, bind_group_layouts: &[&wgpu::BindGroupLayout], fragment_entry_point: &str, topology: wgpu::PrimitiveTopology, polygon_mode: wgpu::PolygonMode) -> wgpu::RenderPipeline {
 
         let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
             label: Some("Shader"),
@@ -172,7 +252,7 @@ impl Engine {
             },
             fragment: Some(wgpu::FragmentState {
                 module: &shader,
-                entry_point: "fs_main",
+                entry_point: fragment_entry_point,
                 targets: &[wgpu::ColorTargetState {
                     format: surface_config.format,
                     blend: Some(wgpu::BlendState::REPLACE),
@@ -180,18 +260,20 @@ impl Engine {
                 }]
             }),
             primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
+                topology,
                 strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Front),
-                polygon_mode: wgpu::PolygonMode::Fill,
+                // lines and points have no side to cull
+                cull_mode: if topology == wgpu::PrimitiveTopology::TriangleList { Some(wgpu::Face::Front) } else { None },
+                polygon_mode,
                 clamp_depth: false,
                 conservative: false
             },
             depth_stencil: Some(wgpu::DepthStencilState {
                 format: texture::Texture::DEPTH_FORMAT,
                 depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
+                // the highlight is drawn over surfaces already in the depth buffer
+                depth_compare: wgpu::CompareFunction::LessEqual,
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default()
             }),
@@ -215,7 +297,134 @@ impl Engine {
     }
 
     pub fn input(&mut self, event: &DeviceEvent) -> bool {
-        self.camera.process_input(event)
+        match event {
+            DeviceEvent::Key(
+                KeyboardInput {
+                    virtual_keycode: Some(key),
+                    state: ElementState::Pressed,
+                    ..
+                }
+            ) if self.process_submesh_key(*key) || self.process_display_key(*key) || self.process_framing_key(*key) => true,
+            _ => self.camera.process_input(event)
+        }
+    }
+
+    // tab selects the next submesh, h hides or shows the selected one and u shows all of them again
+    fn process_submesh_key(&mut self, key: VirtualKeyCode) -> bool {
+        match key {
+            VirtualKeyCode::Tab => {
+                self.select_next_submesh();
+                true
+            }
+            VirtualKeyCode::H => {
+                if let Some((model, submesh)) = self.selected {
+                    let visible = self.models[model].is_submesh_visible(submesh);
+                    self.models[model].set_submesh_visible(submesh, !visible);
+                }
+                true
+            }
+            VirtualKeyCode::U => {
+                for model in &mut self.models {
+                    for submesh in 0..model.get_submeshes().len() {
+                        model.set_submesh_visible(submesh, true);
+                    }
+                }
+                true
+            }
+            _ => false
+        }
+    }
+
+    // c switches between vertex and material colours
+    fn process_display_key(&mut self, key: VirtualKeyCode) -> bool {
+        match key {
+            VirtualKeyCode::C => {
+                self.vertex_colors = !self.vertex_colors;
+                for model in &mut self.models {
+                    model.set_vertex_colors_enabled(&self.queue, self.vertex_colors);
+                }
+                true
+            }
+            _ => false
+        }
+    }
+
+    // home frames the whole scene, f the selected submesh or the whole scene without a selection
+    fn process_framing_key(&mut self, key: VirtualKeyCode) -> bool {
+        match key {
+            VirtualKeyCode::Home => {
+                self.frame_all();
+                true
+            }
+            VirtualKeyCode::F => {
+                self.frame_selection();
+                true
+            }
+            _ => false
+        }
+    }
+
+    // the box and sphere of every model across every instance. the union of the spheres of a
+    // row of instances is much larger than the sphere around their box, so the tighter one is kept
+    pub fn get_scene_bounds(&self) -> (model::Aabb, model::BoundingSphere) {
+
+        let mut bounds = model::Aabb::empty();
+        let mut sphere = model::BoundingSphere::empty();
+        for (model, instances) in self.models.iter().zip(&self.instances) {
+            for instance in instances {
+                let matrix = instance.to_matrix();
+                bounds = bounds.union(&model.get_bounds().transform(&matrix));
+                sphere = sphere.union(&model.get_bounding_sphere().transform(&matrix));
+            }
+        }
+        let box_sphere = bounds.bounding_sphere();
+        (bounds, if box_sphere.radius < sphere.radius { box_sphere } else { sphere })
+    }
+
+    pub fn frame_all(&mut self) {
+
+        let (bounds, sphere) = self.get_scene_bounds();
+        log::info!("framing the scene spanning {:?} to {:?}", bounds.min, bounds.max);
+        self.camera.frame(&sphere);
+    }
+
+    // every instance draws the selection, the one closest to the camera is framed
+    pub fn frame_selection(&mut self) {
+
+        let (model, submesh) = match self.selected {
+            Some(selected) => selected,
+            None => return self.frame_all()
+        };
+        let sphere = self.models[model].get_submeshes()[submesh].bounds.bounding_sphere();
+        let camera = self.camera.get_position();
+        let closest = self.instances[model].iter()
+            .map(|instance| sphere.transform(&instance.to_matrix()))
+            .min_by(|a, b| {
+                let distance = |sphere: &model::BoundingSphere| (cgmath::Point3::from(sphere.center) - camera).magnitude2();
+                distance(a).total_cmp(&distance(b))
+            });
+        if let Some(sphere) = closest {
+            self.camera.frame(&sphere);
+        }
+    }
+
+    // walks through the submeshes of every model, going back to no selection after the last one
+    fn select_next_submesh(&mut self) {
+
+        let (mut model, mut submesh) = match self.selected {
+            Some((model, submesh)) => (model, submesh + 1),
+            None => (0, 0)
+        };
+        while model < self.models.len() && submesh >= self.models[model].get_submeshes().len() {
+            model += 1;
+            submesh = 0;
+        }
+        self.selected = if model < self.models.len() { Some((model, submesh)) } else { None };
+
+        if let Some((model, submesh)) = self.selected {
+            let submesh = &self.models[model].get_submeshes()[submesh];
+            log::info!("selected submesh `{}` spanning {:?} to {:?}", submesh.name, submesh.bounds.min, submesh.bounds.max);
+        }
     }
 
     pub fn update(&mut self, dt: std::time::Duration) {
@@ -261,11 +470,43 @@ impl Engine {
             render_pass.set_bind_group(0, self.camera.get_bind_group(), &[]);
             render_pass.set_bind_group(1, self.light.get_bind_group(), &[]);
 
-            for model in &self.models {
+            for (i, model) in self.models.iter().enumerate() {
+                let instances = 0..self.instances[i].len() as u32;
+                render_pass.set_vertex_buffer(0, model.get_vertex_buffer().slice(..));
+                render_pass.set_vertex_buffer(1, self.instance_buffers[i].slice(..));
+                render_pass.set_index_buffer(model.get_index_buffer().slice(..), model.get_index_format());
+                for (j, submesh) in model.get_submeshes().iter().enumerate() {
+                    if !model.is_submesh_visible(j) {
+                        continue;
+                    }
+                    for range in &submesh.material_ranges {
+                        render_pass.set_bind_group(2, model.get_materials()[range.material].get_bind_group(), &[]);
+                        render_pass.draw_indexed(range.indices.clone(), 0, instances.clone());
+                    }
+                }
+            }
+
+            for (i, model) in self.models.iter().enumerate() {
+                let streams = [(&self.line_pipeline, model.get_line_indices()), (&self.point_pipeline, model.get_point_indices())];
+                for (pipeline, indices) in streams {
+                    if let Some((index_buffer, count)) = indices {
+                        render_pass.set_pipeline(pipeline);
+                        render_pass.set_vertex_buffer(0, model.get_vertex_buffer().slice(..));
+                        render_pass.set_vertex_buffer(1, self.instance_buffers[i].slice(..));
+                        render_pass.set_index_buffer(index_buffer.slice(..), model.get_index_format());
+                        render_pass.draw_indexed(0..count, 0, 0..self.instances[i].len() as u32);
+                    }
+                }
+            }
+
+            // the selected submesh is outlined even while hidden, so it can still be found
+            if let Some((i, submesh)) = self.selected {
+                let model = &self.models[i];
+                render_pass.set_pipeline(&self.highlight_pipeline);
                 render_pass.set_vertex_buffer(0, model.get_vertex_buffer().slice(..));
-                render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-                render_pass.set_index_buffer(model.get_index_buffer().slice(..), wgpu::IndexFormat::Uint32);
-                render_pass.draw_indexed(0..model.get_index_buffer_len(), 0, 0..NUM_INSTANCES as u32);
+                render_pass.set_vertex_buffer(1, self.instance_buffers[i].slice(..));
+                render_pass.set_index_buffer(model.get_index_buffer().slice(..), model.get_index_format());
+                render_pass.draw_indexed(model.get_submeshes()[submesh].indices.clone(), 0, 0..self.instances[i].len() as u32);
             }
         }
 
@@ -276,6 +517,6 @@ impl Engine {
         Ok(())
     }
     pub fn get_size(&self) -> winit::dpi::PhysicalSize<u32> {
-        self.window_size.clone()
+        self.window_size
     }
 }
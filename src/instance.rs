@@ -1,3 +1,5 @@
+use cgmath::{Matrix, SquareMatrix};
+
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct InstanceRaw {
@@ -33,7 +35,7 @@ impl InstanceRaw {
                     shader_location: 5,
                     format: wgpu::VertexFormat::Float32x4,
                 },
-                // normal (rotation)
+                // normal (inverse transpose of rotation and scale)
                 wgpu::VertexAttribute {
                     offset: std::mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
                     shader_location: 6,
@@ -54,6 +56,7 @@ impl InstanceRaw {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Instance {
     pub position: cgmath::Vector3<f32>,
     pub rotation: cgmath::Quaternion<f32>,
@@ -62,13 +65,33 @@ pub struct Instance {
 
 impl Instance {
 
+    // leaves a model where it is
+    pub fn identity() -> Self {
+        Self {
+            position: cgmath::Vector3::new(0.0, 0.0, 0.0),
+            rotation: cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            scaling: cgmath::Vector3::new(1.0, 1.0, 1.0)
+        }
+    }
+
+    pub fn to_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.position) *
+        cgmath::Matrix4::from(self.rotation) *
+        cgmath::Matrix4::from_nonuniform_scale(self.scaling.x, self.scaling.y, self.scaling.z)
+    }
+
+    // normals and tangents stay perpendicular to the surface under non-uniform scaling only
+    // when transformed by the inverse transpose, which is the rotation for uniform scaling
+    pub fn to_normal_matrix(&self) -> cgmath::Matrix3<f32> {
+        let rotation = cgmath::Matrix3::from(self.rotation);
+        let linear = rotation * cgmath::Matrix3::from_diagonal(self.scaling);
+        linear.invert().map(|inverse| inverse.transpose()).unwrap_or(rotation)
+    }
+
     pub fn to_raw(&self) -> InstanceRaw {
-        let model = cgmath::Matrix4::from_translation(self.position) *
-                    cgmath::Matrix4::from(self.rotation) *
-                    cgmath::Matrix4::from_nonuniform_scale(self.scaling.x, self.scaling.y, self.scaling.z);
         InstanceRaw {
-            model:  model.into(),
-            normal: cgmath::Matrix3::from(self.rotation).into(),
+            model:  self.to_matrix().into(),
+            normal: self.to_normal_matrix().into(),
         }
     }
 }
@@ -0,0 +1,8 @@
+pub mod analysis;
+pub mod engine;
+pub mod camera;
+pub mod model;
+pub mod instance;
+pub mod light;
+pub mod material;
+pub mod texture;
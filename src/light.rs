@@ -37,7 +37,7 @@ impl LightData {
         }
     }
 
-    fn into_uniform(&self) -> LightUniform {
+    fn to_uniform(&self) -> LightUniform {
        LightUniform::new([self.position.x, self.position.y, self.position.z], [self.color.0, self.color.1, self.color.2])
     }
 }
@@ -54,7 +54,7 @@ impl Light {
 
     pub fn new(device: &wgpu::Device, data: LightData) -> (Self, wgpu::BindGroupLayout) {
 
-        let mut uniform = data.into_uniform();
+        let uniform = data.to_uniform();
 
         let buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
@@ -106,6 +106,10 @@ impl Light {
     pub fn get_bind_group(&self) -> &wgpu::BindGroup {
         &self.bind_group
     }
+    pub fn set_position<P: Into<cgmath::Point3<f32>>>(&mut self, position: P) {
+        self.data.position = position.into();
+        self.uniform = self.data.to_uniform();
+    }
     pub fn update_buffers(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
 
         // create staging buffer with new data
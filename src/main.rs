@@ -6,21 +6,129 @@ use winit::event::KeyboardInput;
 use winit::event::Event;
 use winit::event::ElementState;
 
-mod engine;
-mod camera;
-mod model;
-mod instance;
-mod light;
-mod texture;
+use agr::{analysis, engine, model};
+
+struct Args {
+    model: String,
+    load_options: model::LoadOptions,
+    // file the processed model is written to instead of being shown
+    export: Option<String>,
+    export_options: model::ExportOptions,
+    // prints statistics of the model instead of showing it, after exporting it when both are
+    // asked for. as JSON if `json` is set, which implies `analyze`
+    analyze: bool,
+    json: bool
+}
+
+fn parse_args() -> Args {
+
+    let mut model = None;
+    let mut options = model::LoadOptions::default();
+    let mut export = None;
+    let mut export_options = model::ExportOptions::default();
+    let mut analyze = false;
+    let mut json = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // refuse to open files with malformed lines instead of skipping them
+            "--strict" => options.mode = model::ParseMode::Strict,
+            "--crease-angle" => {
+                let degrees = args.next().and_then(|s| s.parse::<f32>().ok()).expect("--crease-angle expects an angle in degrees");
+                options.crease_angle = cgmath::Deg(degrees);
+            },
+            "--weld" => {
+                let epsilon = args.next().and_then(|s| s.parse::<f32>().ok()).expect("--weld expects a distance");
+                options.weld_epsilon = Some(epsilon);
+            },
+            "--no-weld" => options.weld_epsilon = None,
+            "--export" => export = Some(args.next().expect("--export expects a file name")),
+            "--export-precision" => {
+                export_options.precision = args.next().and_then(|s| s.parse::<usize>().ok()).expect("--export-precision expects a number of digits");
+            },
+            "--export-no-normals" => export_options.normals = false,
+            "--export-no-groups" => export_options.groups = false,
+            "--no-cache" => options.use_cache = false,
+            "--color-by" => options.color_by = Some(args.next().expect("--color-by expects a vertex property name")),
+            "--analyze" => analyze = true,
+            "--json" => {
+                analyze = true;
+                json = true;
+            },
+            "--purge-cache" => {
+                let cache = model::MeshCache::new(model::MeshCache::default_dir());
+                match cache.purge() {
+                    Ok(removed) => println!("removed {} cached meshes from {}", removed, cache.get_dir().display()),
+                    Err(err) => eprintln!("could not purge {}: {}", cache.get_dir().display(), err)
+                }
+                std::process::exit(0);
+            },
+            _ if !arg.starts_with("--") && model.is_none() => model = Some(arg),
+            _ => eprintln!("ignoring unknown argument `{}`", arg)
+        }
+    }
+    Args {
+        model: model.unwrap_or_else(|| engine::MODEL_FILE.to_string()),
+        load_options: options,
+        export,
+        export_options,
+        analyze,
+        json
+    }
+}
+
+fn load(filename: &str, load_options: &model::LoadOptions) -> Result<model::MeshData, model::ObjError> {
+
+    let (data, report) = model::load(filename, load_options)?;
+    for warning in &report.warnings {
+        log::warn!("{}", warning);
+    }
+    Ok(data)
+}
+
+// exports and reports on the model as it would be shown, after triangulating, welding and
+// generating normals, so welding changes the counts
+fn process(args: &Args) -> Result<(), model::ObjError> {
+
+    let data = load(&args.model, &args.load_options)?;
+    if let Some(filename) = &args.export {
+        data.save_obj(filename, &args.export_options)?;
+    }
+    if args.analyze {
+        let report = analysis::analyze(&data);
+        if args.json {
+            report.write_json(std::io::stdout().lock()).map_err(|source| model::ObjError::Io { file: "stdout".to_string(), source })?;
+        } else {
+            print!("{}", report);
+        }
+    }
+    Ok(())
+}
 
 fn main() {
     env_logger::init();
 
 
+    let args = parse_args();
+    if args.export.is_some() || args.analyze {
+        if let Err(err) = process(&args) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let Args { model, load_options, .. } = args;
+
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new().build(&event_loop).unwrap();
     
-    let mut engine = pollster::block_on(engine::Engine::new(&window));
+    let mut engine = match pollster::block_on(engine::Engine::new(&window, &model, &load_options)) {
+        Ok(engine) => engine,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
     let mut last_render_time = std::time::Instant::now();
     event_loop.run(move |event, _, control_flow| {
 
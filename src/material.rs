@@ -0,0 +1,169 @@
+use wgpu::util::DeviceExt;
+
+use crate::model::MaterialData;
+use crate::texture::Texture;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct MaterialUniform {
+
+    ambient: [f32; 3],
+    shininess: f32,
+    diffuse: [f32; 3],
+    dissolve: f32,
+    specular: [f32; 3],
+    illumination: u32,
+    // non-zero to use the vertex colours in place of the ambient and diffuse colours
+    vertex_colors: u32,
+    // non-zero to perturb the normals with the normal map
+    normal_map: u32,
+    _padding: [u32; 2]
+}
+
+impl MaterialUniform {
+
+    fn new(data: &MaterialData, vertex_colors: bool, normal_map: bool) -> Self {
+
+        Self {
+            ambient: data.ambient,
+            shininess: data.shininess,
+            diffuse: data.diffuse,
+            dissolve: data.dissolve,
+            specular: data.specular,
+            illumination: data.illumination,
+            vertex_colors: vertex_colors as u32,
+            normal_map: normal_map as u32,
+            _padding: [0; 2]
+        }
+    }
+}
+
+pub struct Material {
+
+    uniform: MaterialUniform,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup
+}
+
+impl Material {
+
+    // all materials share one layout, so it is created once by the engine
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None
+                },
+                // diffuse map, multiplied with the diffuse colour
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true }
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false
+                    },
+                    count: None
+                },
+                // tangent space normal map, stored linear
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true }
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false
+                    },
+                    count: None
+                }
+            ],
+            label: Some("material_bind_group_layout")
+        })
+    }
+
+    // materials without a diffuse map are given a white texture. without a normal map the diffuse
+    // map is bound in its place, the shader doesn't sample it then
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, data: &MaterialData, vertex_colors: bool, diffuse_map: &Texture, normal_map: Option<&Texture>) -> Self {
+
+        let uniform = MaterialUniform::new(data, vertex_colors, normal_map.is_some());
+        let normal_map = normal_map.unwrap_or(diffuse_map);
+
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("Material Buffer ({})", data.name)),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+            }
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&diffuse_map.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(diffuse_map.get_sampler())
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&normal_map.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(normal_map.get_sampler())
+                }
+            ],
+            label: Some("material_bind_group")
+        });
+
+        Self {
+            uniform,
+            buffer,
+            bind_group
+        }
+    }
+
+    pub fn set_vertex_colors(&mut self, queue: &wgpu::Queue, enabled: bool) {
+
+        self.uniform.vertex_colors = enabled as u32;
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+
+    pub fn get_bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
@@ -1,6 +1,38 @@
-use std::{fs::File, io::{BufRead, BufReader}};
+use std::{fs::File, io::{self, BufRead, BufWriter, Read, Write}, ops::Range, path::Path, sync::Arc};
 
+use cgmath::{InnerSpace, SquareMatrix, Transform};
 use wgpu::util::DeviceExt;
+
+use crate::instance::Instance;
+use crate::material::Material;
+use crate::texture::{ColorSpace, SamplerOptions, Texture};
+
+mod bounds;
+mod cache;
+mod error;
+mod export;
+mod files;
+mod gltf;
+mod importer;
+mod mtl;
+mod normals;
+mod obj;
+mod ply;
+mod stl;
+mod tangents;
+mod tokenizer;
+mod triangulate;
+mod weld;
+
+pub use bounds::{Aabb, BoundingSphere};
+pub use cache::MeshCache;
+pub use error::{LoadReport, Location, ObjError, ParseMode};
+pub use export::ExportOptions;
+pub use files::{FileSystem, LocalFiles, ZipFiles};
+pub use importer::{ImporterRegistry, ModelImporter};
+pub use mtl::{MaterialData, TextureMap};
+pub use weld::WeldReport;
+
 // represents a type of vertex, and thus must be able to describe a buffer layout for it
 pub trait Vertex: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable {
     fn describe<'a>() -> wgpu::VertexBufferLayout<'a>;
@@ -16,7 +48,21 @@ pub trait Model: Mesh {
 
     fn get_vertex_buffer(&self) -> &wgpu::Buffer;
     fn get_index_buffer(&self) -> &wgpu::Buffer;
-    fn get_index_buffer_len(&self) -> u32;
+    // format of the triangle, line and point index buffers alike
+    fn get_index_format(&self) -> wgpu::IndexFormat;
+    // index buffer and index count of the line segments and points, None when there are none
+    fn get_line_indices(&self) -> Option<(&wgpu::Buffer, u32)>;
+    fn get_point_indices(&self) -> Option<(&wgpu::Buffer, u32)>;
+    fn get_materials(&self) -> &[Material];
+    fn get_submeshes(&self) -> &[Submesh];
+    fn is_submesh_visible(&self, submesh: usize) -> bool;
+    fn set_submesh_visible(&mut self, submesh: usize, visible: bool);
+    fn has_vertex_colors(&self) -> bool;
+    // switches the materials between shading with the vertex colours and with their own colour
+    fn set_vertex_colors_enabled(&mut self, queue: &wgpu::Queue, enabled: bool);
+    // bounds of every vertex, in model space
+    fn get_bounds(&self) -> Aabb;
+    fn get_bounding_sphere(&self) -> BoundingSphere;
 }
 
 struct MeshBufferFactory {}
@@ -30,11 +76,30 @@ impl MeshBufferFactory {
             }
         )
     }
-    fn create_index_buffer(indices: &[u32], device: &wgpu::Device) -> wgpu::Buffer {
+
+    // 16 bit indices take half the memory, and can address every vertex of most meshes.
+    // 0xFFFF is left unused, some backends read it as a primitive restart
+    fn index_format(vertex_count: usize) -> wgpu::IndexFormat {
+        if vertex_count <= u16::MAX as usize {
+            wgpu::IndexFormat::Uint16
+        } else {
+            wgpu::IndexFormat::Uint32
+        }
+    }
+    fn create_index_buffer(indices: &[u32], format: wgpu::IndexFormat, device: &wgpu::Device) -> wgpu::Buffer {
+        let narrowed;
+        let contents = match format {
+            wgpu::IndexFormat::Uint16 => {
+                narrowed = indices.iter().map(|&index| index as u16).collect::<Vec<u16>>();
+                bytemuck::cast_slice(&narrowed)
+            }
+            wgpu::IndexFormat::Uint32 => bytemuck::cast_slice(indices)
+        };
+        // the buffer is padded to a multiple of 4 bytes for an odd number of 16 bit indices
         device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
                 label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(indices),
+                contents,
                 usage: wgpu::BufferUsages::INDEX
             }
         )
@@ -45,14 +110,21 @@ impl MeshBufferFactory {
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct ModelVertex {
     position: [f32; 3],
-    normal: [f32; 3]
+    normal: [f32; 3],
+    color: [f32; 3],
+    tex_coords: [f32; 2],
+    // w is the sign of the bitangent
+    tangent: [f32; 4]
 }
 
 impl ModelVertex {
-    fn new(position: [f32; 3], normal: [f32; 3]) -> Self {
+    fn new(position: [f32; 3], normal: [f32; 3], color: [f32; 3], tex_coords: [f32; 2], tangent: [f32; 4]) -> Self {
         Self {
             position,
-            normal
+            normal,
+            color,
+            tex_coords,
+            tangent
         }
     }
 }
@@ -73,17 +145,561 @@ impl Vertex for ModelVertex {
                     offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
                     shader_location: 1,
                     format: wgpu::VertexFormat::Float32x3
+                },
+                // locations 2 to 8 are taken by the instance data
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x3
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32x2
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 11]>() as wgpu::BufferAddress,
+                    shader_location: 11,
+                    format: wgpu::VertexFormat::Float32x4
                 }
             ]
         }
     } 
 }
 
+// a range of the index buffer drawn with a single material
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialRange {
+    pub material: usize,
+    pub indices: Range<u32>
+}
+
+// a per-vertex value without a place in the vertex buffer, such as the confidence of a scanned
+// point. it can be shown through the vertex colours with `MeshData::color_by_scalar`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScalarProperty {
+    pub name: String,
+    pub values: Vec<f32>
+}
+
+// a named part of a model (an OBJ `o` or `g`), covering a contiguous range of the index buffer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Submesh {
+    pub name: String,
+    pub indices: Range<u32>,
+    // sub-ranges of `indices`, each drawn with one material
+    pub material_ranges: Vec<MaterialRange>,
+    pub bounds: Aabb
+}
+
+// everything parsed from a model file, without any GPU resources. all vertex attributes
+// are indexed the same way, `texcoords` and `colors` are left empty when the file has none,
+// and `tangents` when there are no texcoords to derive them from. `scalars` hold one value per
+// vertex each
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshData {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub texcoords: Vec<[f32; 2]>,
+    pub colors: Vec<[f32; 3]>,
+    pub tangents: Vec<[f32; 4]>,
+    pub scalars: Vec<ScalarProperty>,
+    pub indices: Vec<u32>,
+    // pairs of indices for each line segment, and single ones for points
+    pub line_indices: Vec<u32>,
+    pub point_indices: Vec<u32>,
+    pub submeshes: Vec<Submesh>,
+    pub materials: Vec<MaterialData>
+}
+
+impl MeshData {
+
+    pub fn from_obj<R: BufRead>(mut reader: R, filename: &str, options: &LoadOptions) -> Result<(Self, LoadReport), ObjError> {
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(|source| ObjError::Io { file: filename.to_string(), source })?;
+        Self::from_obj_bytes(&bytes, filename, options, &|_| ())
+    }
+
+    // `progress` is called from the parsing threads with the fraction of the file parsed so far
+    pub fn from_obj_bytes(bytes: &[u8], filename: &str, options: &LoadOptions, progress: &(dyn Fn(f32) + Sync)) -> Result<(Self, LoadReport), ObjError> {
+
+        let (mesh, report) = obj::parse(bytes, filename, options, progress)?;
+        mesh.finish(report, filename, options)
+    }
+
+    pub fn from_stl<R: BufRead>(mut reader: R, filename: &str, options: &LoadOptions) -> Result<(Self, LoadReport), ObjError> {
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(|source| ObjError::Io { file: filename.to_string(), source })?;
+        Self::from_stl_bytes(&bytes, filename, options)
+    }
+
+    // ASCII or binary, told apart by the contents rather than the name
+    pub fn from_stl_bytes(bytes: &[u8], filename: &str, options: &LoadOptions) -> Result<(Self, LoadReport), ObjError> {
+
+        let (mesh, report) = stl::parse(bytes, filename, options)?;
+        mesh.finish(report, filename, options)
+    }
+
+    pub fn from_ply<R: BufRead>(mut reader: R, filename: &str, options: &LoadOptions) -> Result<(Self, LoadReport), ObjError> {
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(|source| ObjError::Io { file: filename.to_string(), source })?;
+        Self::from_ply_bytes(&bytes, filename, options)
+    }
+
+    pub fn from_ply_bytes(bytes: &[u8], filename: &str, options: &LoadOptions) -> Result<(Self, LoadReport), ObjError> {
+
+        let (mesh, report) = ply::parse(bytes, filename, options)?;
+        mesh.finish(report, filename, options)
+    }
+
+    // the whole scene of a .gltf or .glb file as one mesh, see `SceneData::flatten`
+    pub fn from_gltf_bytes(bytes: &[u8], filename: &str, options: &LoadOptions) -> Result<(Self, LoadReport), ObjError> {
+
+        let (scene, report) = SceneData::from_gltf_bytes(bytes, filename, options)?;
+        Ok((scene.into_mesh(), report))
+    }
+
+    // reads any format `options.importers` has an importer for, a scene as one mesh
+    pub fn from_bytes(bytes: &[u8], filename: &str, options: &LoadOptions, progress: &(dyn Fn(f32) + Sync)) -> Result<(Self, LoadReport), ObjError> {
+
+        let (scene, report) = SceneData::from_bytes(bytes, filename, options, progress)?;
+        Ok((scene.into_mesh(), report))
+    }
+
+    pub fn from_file(filename: &str, options: &LoadOptions) -> Result<(Self, LoadReport), ObjError> {
+
+        let (scene, report) = SceneData::from_file(filename, options)?;
+        Ok((scene.into_mesh(), report))
+    }
+
+    pub fn from_reader<R: Read>(reader: R, filename: &str, options: &LoadOptions) -> Result<(Self, LoadReport), ObjError> {
+
+        let (scene, report) = SceneData::from_reader(reader, filename, options)?;
+        Ok((scene.into_mesh(), report))
+    }
+
+    // the processing every format goes through after parsing. importers may leave the normals
+    // out, they are generated then
+    fn finish(mut self, mut report: LoadReport, filename: &str, options: &LoadOptions) -> Result<(Self, LoadReport), ObjError> {
+
+        self.check(filename)?;
+        if self.normals.is_empty() {
+            self.generate_normals(options.crease_angle.into());
+        }
+        // added to what the importer counted, such as the OBJ positions no element used
+        if let Some(epsilon) = options.weld_epsilon {
+            report.weld += self.weld(epsilon);
+        }
+        self.generate_tangents();
+        Ok((self, report))
+    }
+
+    // every attribute holds a value for each position, or none at all, and every index refers
+    // to a position. welding and uploading rely on both
+    fn check(&self, filename: &str) -> Result<(), ObjError> {
+
+        let count = self.positions.len();
+        let optional = [("normals", self.normals.len()), ("texture coordinates", self.texcoords.len()), ("colours", self.colors.len()), ("tangents", self.tangents.len())];
+        let mismatch = optional.into_iter().find(|&(_, len)| len != 0 && len != count)
+            .or_else(|| self.scalars.iter().map(|scalar| (scalar.name.as_str(), scalar.values.len())).find(|&(_, len)| len != count));
+        if let Some((name, len)) = mismatch {
+            return Err(ObjError::invalid_data(filename, format!("the mesh has {} values of {} for {} positions", len, name, count)));
+        }
+        if let Some(index) = self.indices.iter().chain(&self.line_indices).chain(&self.point_indices).find(|&&index| index as usize >= count) {
+            return Err(ObjError::invalid_data(filename, format!("index {} is out of range, the mesh has {} positions", index, count)));
+        }
+        Ok(())
+    }
+
+    pub fn from_obj_file(filename: &str, options: &LoadOptions) -> Result<(Self, LoadReport), ObjError> {
+        Self::from_obj_file_with_progress(filename, options, &|_| ())
+    }
+
+    pub fn from_obj_file_with_progress(filename: &str, options: &LoadOptions, progress: &(dyn Fn(f32) + Sync)) -> Result<(Self, LoadReport), ObjError> {
+
+        with_file_bytes(filename, |bytes| Self::from_obj_bytes(bytes, filename, options, progress))
+    }
+
+    pub fn write_obj<W: Write>(&self, writer: W, options: &ExportOptions, mtllib: Option<&str>) -> std::io::Result<()> {
+        export::write_obj(self, writer, options, mtllib)
+    }
+
+    // texture paths are written relative to `dir`, the directory the library is saved in
+    pub fn write_mtl<W: Write>(&self, writer: W, options: &ExportOptions, dir: &Path) -> std::io::Result<()> {
+        export::write_mtl(&self.materials, writer, options, dir)
+    }
+
+    // writes the mesh to `filename`, and its materials to a library with the same name
+    // ending in .mtl when the options ask for them
+    pub fn save_obj(&self, filename: &str, options: &ExportOptions) -> Result<(), ObjError> {
+
+        let create = |path: &Path| {
+            let file = File::create(path).map_err(|source| ObjError::Io { file: path.to_string_lossy().to_string(), source })?;
+            Ok(BufWriter::new(file))
+        };
+        let path = Path::new(filename);
+        // the library is named after the model, without the spaces `mtllib` can't hold
+        let library = path.with_extension("mtl").file_name().map(|name| path.with_file_name(export::escape(&name.to_string_lossy()).as_ref()));
+        let library = library.filter(|_| options.materials && !self.materials.is_empty());
+        let mtllib = library.as_ref().and_then(|library| library.file_name()).map(|name| name.to_string_lossy().to_string());
+        if let Some(library) = &library {
+            let dir = library.parent().unwrap_or_else(|| Path::new(""));
+            self.write_mtl(create(library)?, options, dir).map_err(|source| ObjError::Io { file: library.to_string_lossy().to_string(), source })?;
+        }
+        self.write_obj(create(path)?, options, mtllib.as_deref()).map_err(|source| ObjError::Io { file: filename.to_string(), source })
+    }
+
+    // smooth normals split where faces meet at more than `crease_angle`, for meshes without any
+    pub fn generate_normals(&mut self, crease_angle: cgmath::Rad<f32>) {
+        normals::fill(self, crease_angle)
+    }
+
+    // MikkTSpace tangents for normal mapping, splitting vertices where the texture is mirrored
+    pub fn generate_tangents(&mut self) {
+        tangents::generate(self)
+    }
+
+    pub fn has_tangents(&self) -> bool {
+        !self.tangents.is_empty()
+    }
+
+    // merges vertices with attributes at most `epsilon` apart and drops unreferenced ones
+    pub fn weld(&mut self, epsilon: f32) -> WeldReport {
+        weld::weld(self, epsilon)
+    }
+
+    pub fn has_colors(&self) -> bool {
+        !self.colors.is_empty()
+    }
+
+    pub fn get_scalar(&self, name: &str) -> Option<&ScalarProperty> {
+        self.scalars.iter().find(|scalar| scalar.name == name)
+    }
+
+    // replaces the vertex colours with a ramp from blue through green to red over the range of
+    // a scalar property, false when there is none of that name
+    pub fn color_by_scalar(&mut self, name: &str) -> bool {
+
+        let values = match self.get_scalar(name) {
+            Some(scalar) => &scalar.values,
+            None => return false
+        };
+        let (min, max) = values.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &value| (min.min(value), max.max(value)));
+        let range = if max > min { max - min } else { 1.0 };
+        self.colors = values.iter().map(|&value| {
+            let t = (value - min) / range;
+            if t < 0.5 {
+                [0.0, t * 2.0, 1.0 - t * 2.0]
+            } else {
+                [t * 2.0 - 1.0, 2.0 - t * 2.0, 0.0]
+            }
+        }).collect();
+        true
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_points(&self.positions)
+    }
+
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere::from_points(&self.positions)
+    }
+
+    // vertices without a colour are white, so they show the plain material colour either way
+    pub fn vertices(&self) -> Vec<ModelVertex> {
+        self.positions.iter().zip(self.normals.iter()).enumerate().map(|(i, (position, normal))| {
+            // OBJ puts v = 0 at the bottom of the image, wgpu at the top
+            let [u, v] = self.texcoords.get(i).copied().unwrap_or([0.0; 2]);
+            let tangent = self.tangents.get(i).copied().unwrap_or([1.0, 0.0, 0.0, 1.0]);
+            ModelVertex::new(*position, *normal, self.colors.get(i).copied().unwrap_or([1.0; 3]), [u, 1.0 - v], tangent)
+        }).collect()
+    }
+}
+
+// the meshes of a file with a scene graph, and where each of them is drawn
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneData {
+    pub meshes: Vec<MeshData>,
+    // `instances[i]` places `meshes[i]`, a mesh no node refers to has none. None for formats
+    // without a scene graph, which leave the layout to the viewer
+    pub instances: Option<Vec<Vec<Instance>>>
+}
+
+impl SceneData {
+
+    pub fn from_gltf_bytes(bytes: &[u8], filename: &str, options: &LoadOptions) -> Result<(Self, LoadReport), ObjError> {
+
+        let (scene, report) = self::gltf::parse(bytes, filename, options)?;
+        scene.finish(report, filename, options)
+    }
+
+    // reads any format `options.importers` has an importer for, also gzip compressed or in a
+    // zip archive with the files it refers to. files are only unpacked when no importer claims
+    // their extension, as a binary STL header may start like either
+    pub fn from_bytes(bytes: &[u8], filename: &str, options: &LoadOptions, progress: &(dyn Fn(f32) + Sync)) -> Result<(Self, LoadReport), ObjError> {
+
+        let claimed = options.importers.find_by_extension(filename).is_some();
+        if !claimed && bytes.starts_with(&[0x1f, 0x8b]) {
+            let mut unpacked = Vec::new();
+            flate2::read::GzDecoder::new(bytes).read_to_end(&mut unpacked).map_err(|source| ObjError::Io { file: filename.to_string(), source })?;
+            // model.obj.gz is read as model.obj, whose libraries are next to it
+            let inner = match Path::new(filename).extension() {
+                Some(extension) if extension.eq_ignore_ascii_case("gz") => &filename[..filename.len() - 3],
+                _ => filename
+            };
+            return Self::from_bytes(&unpacked, inner, options, progress);
+        }
+        if !claimed && bytes.starts_with(b"PK\x03\x04") {
+            let files = Arc::new(ZipFiles::from_reader(io::Cursor::new(bytes), filename)?);
+            let (model, model_bytes) = files.find_model(&options.importers)?;
+            let options = LoadOptions { files: files.clone(), ..options.clone() };
+            return Self::from_bytes(model_bytes, &model.to_string_lossy(), &options, progress);
+        }
+        options.importers.import(bytes, filename, options, progress)
+    }
+
+    pub fn from_file(filename: &str, options: &LoadOptions) -> Result<(Self, LoadReport), ObjError> {
+        Self::from_file_with_progress(filename, options, &|_| ())
+    }
+
+    pub fn from_file_with_progress(filename: &str, options: &LoadOptions, progress: &(dyn Fn(f32) + Sync)) -> Result<(Self, LoadReport), ObjError> {
+        with_file_bytes(filename, |bytes| Self::from_bytes(bytes, filename, options, progress))
+    }
+
+    // reads all of `reader`, such as standard input. the files the model refers to are found
+    // next to `filename` through `options.files`
+    pub fn from_reader<R: Read>(mut reader: R, filename: &str, options: &LoadOptions) -> Result<(Self, LoadReport), ObjError> {
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(|source| ObjError::Io { file: filename.to_string(), source })?;
+        Self::from_bytes(&bytes, filename, options, &|_| ())
+    }
+
+    // `MeshData::finish` for every mesh, with the welding of all of them reported together
+    fn finish(mut self, mut report: LoadReport, filename: &str, options: &LoadOptions) -> Result<(Self, LoadReport), ObjError> {
+
+        self.meshes = self.meshes.into_iter().map(|mesh| {
+            let (mesh, mesh_report) = mesh.finish(LoadReport::default(), filename, options)?;
+            report.weld += mesh_report.weld;
+            Ok(mesh)
+        }).collect::<Result<_, ObjError>>()?;
+        if !options.files.is_local() {
+            self.embed_images(options.files.as_ref(), &mut report);
+        }
+        Ok((self, report))
+    }
+
+    // reads the images of the texture maps into their materials, for file systems the
+    // textures can't be loaded from when the model is uploaded. like missing textures of local
+    // files, images that can't be read are only warned about
+    fn embed_images(&mut self, files: &dyn FileSystem, report: &mut LoadReport) {
+
+        for material in self.meshes.iter_mut().flat_map(|mesh| &mut mesh.materials) {
+            for (map, path) in std::mem::take(&mut material.maps) {
+                match files.read(&path) {
+                    Ok(bytes) => {
+                        material.embedded.insert(map, bytes);
+                    },
+                    Err(source) => report.warnings.push(ObjError::Io { file: path.to_string_lossy().to_string(), source })
+                }
+            }
+        }
+    }
+
+    // the scene as one mesh, taken as it is when it already is one
+    pub fn into_mesh(mut self) -> MeshData {
+
+        if self.instances.is_none() && self.meshes.len() == 1 {
+            return self.meshes.pop().unwrap();
+        }
+        self.flatten()
+    }
+
+    // a single mesh holding every instance of every mesh, for what works on one mesh such as
+    // exporting and analysis. without instances each mesh is taken once as it is. attributes
+    // only some of the meshes have get their default value in the others
+    pub fn flatten(&self) -> MeshData {
+
+        let identity = vec![Instance::identity()];
+        let placements = |i: usize| self.instances.as_ref().map_or(&identity, |instances| &instances[i]);
+        let placed = (0..self.meshes.len()).filter(|&i| !placements(i).is_empty()).collect::<Vec<_>>();
+        let any = |has: fn(&MeshData) -> bool| placed.iter().any(|&i| has(&self.meshes[i]));
+        let (texcoords, colors, tangents) = (any(|mesh| !mesh.texcoords.is_empty()), any(MeshData::has_colors), any(MeshData::has_tangents));
+        // scalars are kept when every mesh has them
+        let scalars = placed.first().map_or(Vec::new(), |&first| {
+            self.meshes[first].scalars.iter()
+                .filter(|scalar| placed.iter().all(|&i| self.meshes[i].get_scalar(&scalar.name).is_some()))
+                .map(|scalar| ScalarProperty { name: scalar.name.clone(), values: Vec::new() })
+                .collect()
+        });
+        let mut flat = MeshData { scalars, ..MeshData::default() };
+
+        for i in placed {
+            let mesh = &self.meshes[i];
+            let material_offset = flat.materials.len();
+            flat.materials.extend(mesh.materials.iter().cloned());
+            let instances = placements(i);
+            for (number, instance) in instances.iter().enumerate() {
+                let matrix = instance.to_matrix();
+                let normal_matrix = instance.to_normal_matrix();
+                // a mirroring instance turns the triangles around, and the bitangents with them
+                let mirrored = normal_matrix.determinant() < 0.0;
+                let base = flat.positions.len() as u32;
+                let index_base = flat.indices.len() as u32;
+
+                flat.positions.extend(mesh.positions.iter().map(|&position| -> [f32; 3] {
+                    matrix.transform_point(cgmath::Point3::from(position)).into()
+                }));
+                flat.normals.extend(mesh.normals.iter().map(|&normal| -> [f32; 3] {
+                    let normal = normal_matrix * cgmath::Vector3::from(normal);
+                    if normal.magnitude2() > 0.0 { normal.normalize().into() } else { normal.into() }
+                }));
+                let count = mesh.positions.len();
+                if texcoords {
+                    flat.texcoords.extend(if mesh.texcoords.is_empty() { vec![[0.0; 2]; count] } else { mesh.texcoords.clone() });
+                }
+                if colors {
+                    flat.colors.extend(if mesh.has_colors() { mesh.colors.clone() } else { vec![[1.0; 3]; count] });
+                }
+                if tangents {
+                    let sign = if mirrored { -1.0 } else { 1.0 };
+                    flat.tangents.extend((0..count).map(|vertex| {
+                        let [x, y, z, w] = mesh.tangents.get(vertex).copied().unwrap_or([1.0, 0.0, 0.0, 1.0]);
+                        let tangent = matrix.transform_vector(cgmath::Vector3::new(x, y, z));
+                        let tangent = if tangent.magnitude2() > 0.0 { tangent.normalize() } else { tangent };
+                        [tangent.x, tangent.y, tangent.z, w * sign]
+                    }));
+                }
+                for scalar in &mut flat.scalars {
+                    scalar.values.extend(&mesh.get_scalar(&scalar.name).unwrap().values);
+                }
+
+                flat.indices.extend(mesh.indices.chunks_exact(3).flat_map(|triangle| {
+                    let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|vertex| base + vertex);
+                    if mirrored { [a, c, b] } else { [a, b, c] }
+                }));
+                flat.line_indices.extend(mesh.line_indices.iter().map(|&vertex| base + vertex));
+                flat.point_indices.extend(mesh.point_indices.iter().map(|&vertex| base + vertex));
+                let shift = |range: &Range<u32>| range.start + index_base..range.end + index_base;
+                flat.submeshes.extend(mesh.submeshes.iter().map(|submesh| Submesh {
+                    name: if instances.len() > 1 { format!("{} {}", submesh.name, number + 1) } else { submesh.name.clone() },
+                    indices: shift(&submesh.indices),
+                    material_ranges: submesh.material_ranges.iter().map(|range| MaterialRange {
+                        material: range.material + material_offset,
+                        indices: shift(&range.indices)
+                    }).collect(),
+                    bounds: submesh.bounds.transform(&matrix)
+                }));
+            }
+        }
+        flat
+    }
+
+    // whether an instance mirrors its mesh. drawn through an instance buffer its triangles
+    // would face the other way and its bitangents flip, only `flatten` turns them around
+    pub fn has_mirrored_instances(&self) -> bool {
+        self.instances.iter().flatten().flatten().any(|instance| instance.scaling.x * instance.scaling.y * instance.scaling.z < 0.0)
+    }
+}
+
+impl From<MeshData> for SceneData {
+
+    fn from(mesh: MeshData) -> Self {
+        Self { meshes: vec![mesh], instances: None }
+    }
+}
+
+// shows the scalar property `options.color_by` through the vertex colours of every mesh having it
+fn apply_color_by(scene: &mut SceneData, filename: &str, options: &LoadOptions) {
+
+    if let Some(name) = &options.color_by {
+        let mut colored = false;
+        for mesh in &mut scene.meshes {
+            colored |= mesh.color_by_scalar(name);
+        }
+        if !colored {
+            let mut names = scene.meshes.iter().flat_map(|mesh| &mesh.scalars).map(|scalar| scalar.name.as_str()).collect::<Vec<_>>();
+            names.sort_unstable();
+            names.dedup();
+            log::warn!("{} has no property `{}` to colour by, it has [{}]", filename, name, names.join(", "));
+        }
+    }
+}
+
+// loads a model file of any format `options.importers` reads, through the cache or not, with
+// its meshes and where its scene places them. `-` reads standard input, which is never cached.
+// `progress` is called from the parsing threads with the fraction of the file parsed so far,
+// and not at all when the scene comes from the cache
+pub fn load_scene(filename: &str, options: &LoadOptions, progress: &(dyn Fn(f32) + Sync)) -> Result<(SceneData, LoadReport), ObjError> {
+
+    let (mut scene, report) = if filename == "-" {
+        SceneData::from_reader(io::stdin().lock(), "stdin", options)?
+    } else if options.use_cache {
+        MeshCache::new(MeshCache::default_dir()).load_scene(filename, options, progress)?
+    } else {
+        SceneData::from_file_with_progress(filename, options, progress)?
+    };
+    apply_color_by(&mut scene, filename, options);
+    Ok((scene, report))
+}
+
+// `load_scene` with the scene as one mesh
+pub fn load(filename: &str, options: &LoadOptions) -> Result<(MeshData, LoadReport), ObjError> {
+
+    let (scene, report) = load_scene(filename, options, &|_| ())?;
+    Ok((scene.into_mesh(), report))
+}
+
+// memory-maps a file rather than reading it, so large files are never copied into memory
+fn with_file_bytes<T>(filename: &str, f: impl FnOnce(&[u8]) -> Result<T, ObjError>) -> Result<T, ObjError> {
+
+    let io_error = |source| ObjError::Io { file: filename.to_string(), source };
+    let file = File::open(filename).map_err(io_error)?;
+    // mapping an empty file fails on some platforms
+    if file.metadata().map_err(io_error)?.len() == 0 {
+        return f(&[]);
+    }
+    // safety: the map is only read while parsing, and files aren't expected to change
+    // while they are being loaded
+    let map = unsafe { memmap2::Mmap::map(&file) }.map_err(io_error)?;
+    f(&map)
+}
+
+// where the image of a texture map is read from, with the material name for embedded ones
+#[derive(PartialEq)]
+enum Image<'a> {
+    File(&'a Path),
+    Embedded(&'a [u8], &'a str)
+}
+
+impl<'a> Image<'a> {
+
+    // a file takes precedence over an embedded image for the same map
+    fn of(material: &'a MaterialData, map: TextureMap) -> Option<Self> {
+        material.maps.get(&map).map(|path| Image::File(path))
+            .or_else(|| material.embedded.get(&map).map(|bytes| Image::Embedded(bytes, &material.name)))
+    }
+}
+
 pub struct SimpleFileModel {
 
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
-    index_buffer_len: u32
+    index_format: wgpu::IndexFormat,
+    line_indices: Option<(wgpu::Buffer, u32)>,
+    point_indices: Option<(wgpu::Buffer, u32)>,
+    materials: Vec<Material>,
+    // kept alive for the material bind groups
+    #[allow(dead_code)]
+    textures: Vec<Texture>,
+    submeshes: Vec<Submesh>,
+    submesh_visibility: Vec<bool>,
+    has_vertex_colors: bool,
+    bounds: Aabb,
+    bounding_sphere: BoundingSphere
 }
 
 impl Mesh for SimpleFileModel {
@@ -99,93 +715,187 @@ impl Model for SimpleFileModel {
         &self.index_buffer
     }
 
-    fn get_index_buffer_len(&self) -> u32 {
-        self.index_buffer_len
+    fn get_index_format(&self) -> wgpu::IndexFormat {
+        self.index_format
+    }
+
+    fn get_line_indices(&self) -> Option<(&wgpu::Buffer, u32)> {
+        self.line_indices.as_ref().map(|(buffer, count)| (buffer, *count))
+    }
+
+    fn get_point_indices(&self) -> Option<(&wgpu::Buffer, u32)> {
+        self.point_indices.as_ref().map(|(buffer, count)| (buffer, *count))
+    }
+
+    fn get_materials(&self) -> &[Material] {
+        &self.materials
+    }
+
+    fn get_submeshes(&self) -> &[Submesh] {
+        &self.submeshes
+    }
+
+    fn is_submesh_visible(&self, submesh: usize) -> bool {
+        self.submesh_visibility[submesh]
+    }
+
+    fn set_submesh_visible(&mut self, submesh: usize, visible: bool) {
+        self.submesh_visibility[submesh] = visible;
+    }
+
+    fn has_vertex_colors(&self) -> bool {
+        self.has_vertex_colors
+    }
+
+    fn set_vertex_colors_enabled(&mut self, queue: &wgpu::Queue, enabled: bool) {
+        for material in &mut self.materials {
+            material.set_vertex_colors(queue, enabled && self.has_vertex_colors);
+        }
+    }
+
+    fn get_bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn get_bounding_sphere(&self) -> BoundingSphere {
+        self.bounding_sphere
     }
 }
 
-impl SimpleFileModel {
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    pub mode: ParseMode,
+    // faces meeting at a sharper angle than this get separate generated normals
+    pub crease_angle: cgmath::Deg<f32>,
+    // vertices closer than this in every attribute are merged, None keeps them all
+    pub weld_epsilon: Option<f32>,
+    // models are loaded from and saved to the default MeshCache
+    pub use_cache: bool,
+    // scalar property shown through the vertex colours. it is applied after the cache, so
+    // cached meshes don't depend on it
+    pub color_by: Option<String>,
+    // what files are read with, other formats are added by registering importers for them
+    pub importers: ImporterRegistry,
+    // where material libraries, textures and other files a model refers to are read from
+    pub files: Arc<dyn FileSystem>
+}
 
-    pub fn new(device: &wgpu::Device, filename: &str) -> Result<Self, std::io::Error> {
-
-        let file = File::open(&filename)?;
-
-        let mut reader = BufReader::new(file);
-        let mut line = String::new();
-        let mut vertices : Vec<[f32; 3]> = Vec::new();
-        let mut vertex_normals : Vec<[f32; 3]> = Vec::new();
-        let mut indices : Vec<u32> = Vec::new();
-        let mut indexed_references : bool = false;
-        loop {
-
-            match reader.read_line(&mut line) {
-                Ok(bytes_read) => {
-                    if bytes_read == 0 {
-                        break;
-                    }
-
-                    match line.remove(0) {
-
-                        'v' => {
-                            match line.remove(0) {
-
-                                'n' => {
-                                    let vert_normal = line.trim().split(' ').filter_map(|s| s.parse::<f32>().ok());
-                                    if vert_normal.clone().count() == 3 {
-                                        let mut final_array : [f32; 3] = [0.0; 3];
-                                        for (i, val) in vert_normal.enumerate() {
-                                            final_array[i] = val;
-                                        }
-                                        vertex_normals.push(final_array);
-                                    }
-                                }
-                                ' ' => {
-                                    let vert = line.trim().split(' ').filter_map(|s| s.parse::<f32>().ok());
-                                    if vert.clone().count() == 3 {
-                                        let mut final_array : [f32; 3] = [0.0; 3];
-                                        for (i, val) in vert.enumerate() {
-                                            final_array[i] = val;
-                                        }
-                                        vertices.push(final_array);
-                                    }
-                                },
-                                _ => ()
-                            }
-                        },
-                        'f' => {
-                            let idxs = line[1..].trim().split(' ').filter_map(|s| s.parse::<u32>().ok());
-                            if idxs.clone().count() == 3 {
-                                indices.extend(idxs.map(|n| n-1).collect::<Vec<u32>>());
-                            }
-                        },
-                        _ => ()
-                    }
-
-                    line.clear();
-                }
-                Err(err) => return Err(err)
-            }
+impl Default for LoadOptions {
+
+    fn default() -> Self {
+        Self {
+            mode: ParseMode::Lenient,
+            crease_angle: cgmath::Deg(60.0),
+            weld_epsilon: Some(0.0),
+            use_cache: true,
+            color_by: None,
+            importers: ImporterRegistry::default(),
+            files: Arc::new(LocalFiles)
         }
+    }
+}
 
-        // if indices don't use references to normals or textures
-        let mut final_vertices : Vec<ModelVertex> = Vec::with_capacity(vertices.len());
-        if !indexed_references && vertex_normals.len() > 0 {
+impl SimpleFileModel {
 
-            for (vert, normal) in vertices.iter().zip(vertex_normals.iter()) {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, filename: &str, options: &LoadOptions) -> Result<(Self, LoadReport), ObjError> {
 
-                final_vertices.push(ModelVertex::new(*vert, *normal));
-            }
-        } else if vertex_normals.len() == 0 {
+        let (data, report) = load(filename, options)?;
+        Ok((Self::from_mesh(device, queue, material_layout, &data), report))
+    }
+
+    // a model read from `reader`, named `filename` to find the files it refers to
+    pub fn from_reader<R: Read>(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, reader: R, filename: &str, options: &LoadOptions) -> Result<(Self, LoadReport), ObjError> {
+
+        let (data, report) = MeshData::from_reader(reader, filename, options)?;
+        Ok((Self::from_mesh(device, queue, material_layout, &data), report))
+    }
 
-            for vert in vertices {
-                final_vertices.push(ModelVertex::new(vert, [0.0, 1.0, 0.0]));
+    // uploads already parsed mesh data
+    pub fn from_mesh(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, data: &MeshData) -> Self {
+
+        let index_format = MeshBufferFactory::index_format(data.positions.len());
+        // most files have no lines or points, so no buffer is created for them
+        let optional_indices = |indices: &[u32]| {
+            if indices.is_empty() {
+                None
+            } else {
+                Some((MeshBufferFactory::create_index_buffer(indices, index_format, device), indices.len() as u32))
+            }
+        };
+        // the first texture is the white one, every image is loaded once however many materials use it
+        let mut textures = vec![Texture::white(device, queue)];
+        let mut loaded_images = Vec::new();
+        let mut load = |image, color_space: ColorSpace| {
+            if let Some(loaded) = loaded_images.iter().position(|(loaded, space)| *loaded == image && *space == color_space) {
+                return Some(loaded + 1);
             }
+            let sampler = SamplerOptions::default();
+            let loaded = match image {
+                Image::File(path) => Texture::from_path(device, queue, path, color_space, &sampler).map_err(|err| format!("could not load {}: {}", path.display(), err)),
+                Image::Embedded(bytes, name) => Texture::from_bytes(device, queue, bytes, name, color_space, &sampler).map_err(|err| format!("could not decode the image of {}: {}", name, err))
+            };
+            match loaded {
+                Ok(texture) => {
+                    textures.push(texture);
+                    loaded_images.push((image, color_space));
+                    Some(textures.len() - 1)
+                }
+                Err(err) => {
+                    log::warn!("{}", err);
+                    None
+                }
+            }
+        };
+        // normal maps need tangents, which are only there when the mesh has texture coordinates.
+        // many files name their normal map with `bump`, so it is used when there is no `norm`
+        let maps = data.materials.iter().map(|material| {
+            let diffuse_map = Image::of(material, TextureMap::Diffuse).and_then(|image| load(image, ColorSpace::Srgb));
+            let normal_map = Image::of(material, TextureMap::Normal)
+                .or_else(|| Image::of(material, TextureMap::Bump))
+                .filter(|_| data.has_tangents())
+                .and_then(|image| load(image, ColorSpace::Linear));
+            (diffuse_map, normal_map)
+        }).collect::<Vec<(Option<usize>, Option<usize>)>>();
+        let materials = data.materials.iter().zip(maps).map(|(material, (diffuse_map, normal_map))| {
+            let normal_map = normal_map.map(|i| &textures[i]);
+            Material::new(device, material_layout, material, data.has_colors(), &textures[diffuse_map.unwrap_or(0)], normal_map)
+        }).collect();
+
+        Self {
+            vertex_buffer: MeshBufferFactory::create_vertex_buffer(&data.vertices()[..], device),
+            index_buffer: MeshBufferFactory::create_index_buffer(&data.indices[..], index_format, device),
+            index_format,
+            line_indices: optional_indices(&data.line_indices),
+            point_indices: optional_indices(&data.point_indices),
+            materials,
+            textures,
+            submeshes: data.submeshes.clone(),
+            submesh_visibility: vec![true; data.submeshes.len()],
+            has_vertex_colors: data.has_colors(),
+            bounds: data.bounds(),
+            bounding_sphere: data.bounding_sphere()
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texture_coordinates_are_flipped_for_wgpu() {
+
+        let source = "v 0 0 0\nv 1 0 0\nv 1 1 0\nvt 0 0\nvt 1 0.25\nvt 1 1\nf 1/1 2/2 3/3\n";
+        let (mesh, _) = MeshData::from_obj(source.as_bytes(), "flip.obj", &LoadOptions::default()).unwrap();
+        let mut tex_coords = mesh.vertices().iter().map(|vertex| vertex.tex_coords).collect::<Vec<_>>();
+        tex_coords.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(tex_coords, vec![[0.0, 1.0], [1.0, 0.0], [1.0, 0.75]]);
+    }
 
-        Ok(Self {
-            vertex_buffer: MeshBufferFactory::create_vertex_buffer(&final_vertices[..], &device),
-            index_buffer: MeshBufferFactory::create_index_buffer(&indices[..], &device),
-            index_buffer_len: indices.len() as u32
-        })
+    #[test]
+    fn sixteen_bit_indices_until_they_cannot_address_every_vertex() {
+        assert_eq!(MeshBufferFactory::index_format(3), wgpu::IndexFormat::Uint16);
+        assert_eq!(MeshBufferFactory::index_format(65535), wgpu::IndexFormat::Uint16);
+        assert_eq!(MeshBufferFactory::index_format(65536), wgpu::IndexFormat::Uint32);
     }
 }
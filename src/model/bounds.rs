@@ -0,0 +1,213 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Transform, Vector3};
+
+// axis aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3]
+}
+
+impl Aabb {
+
+    // an inverted box that any point grows into a valid one
+    pub fn empty() -> Self {
+        Self {
+            min: [f32::INFINITY; 3],
+            max: [f32::NEG_INFINITY; 3]
+        }
+    }
+
+    pub fn from_points<'a, I: IntoIterator<Item = &'a [f32; 3]>>(points: I) -> Self {
+
+        let mut bounds = Self::empty();
+        for point in points {
+            bounds.grow(*point);
+        }
+        bounds
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|i| self.min[i] > self.max[i])
+    }
+
+    pub fn grow(&mut self, point: [f32; 3]) {
+        for (i, value) in point.into_iter().enumerate() {
+            self.min[i] = self.min[i].min(value);
+            self.max[i] = self.max[i].max(value);
+        }
+    }
+
+    // the smallest box holding both, an empty box adds nothing
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: [0, 1, 2].map(|i| self.min[i].min(other.min[i])),
+            max: [0, 1, 2].map(|i| self.max[i].max(other.max[i]))
+        }
+    }
+
+    pub fn center(&self) -> [f32; 3] {
+        [0, 1, 2].map(|i| (self.min[i] + self.max[i]) * 0.5)
+    }
+
+    fn corners(&self) -> [[f32; 3]; 8] {
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|corner: usize| {
+            [0, 1, 2].map(|i| if corner & (1 << i) == 0 { self.min[i] } else { self.max[i] })
+        })
+    }
+
+    // the box around the transformed corners, which is larger than the box itself under rotation
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Aabb {
+
+        if self.is_empty() {
+            return *self;
+        }
+        let mut bounds = Aabb::empty();
+        for corner in self.corners() {
+            bounds.grow(matrix.transform_point(Point3::from(corner)).into());
+        }
+        bounds
+    }
+
+    // the sphere through the corners, looser than `BoundingSphere::from_points` for the same points
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+
+        if self.is_empty() {
+            return BoundingSphere::empty();
+        }
+        let center = Point3::from(self.center());
+        BoundingSphere {
+            center: center.into(),
+            radius: (Point3::from(self.max) - center).magnitude()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: [f32; 3],
+    pub radius: f32
+}
+
+impl BoundingSphere {
+
+    // a negative radius marks a sphere holding nothing
+    pub fn empty() -> Self {
+        Self {
+            center: [0.0; 3],
+            radius: -1.0
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.radius < 0.0
+    }
+
+    // Ritter's approximation: starts from two far apart points and grows to take in the others.
+    // it is at most a few percent larger than the smallest sphere, and takes two passes
+    pub fn from_points(points: &[[f32; 3]]) -> Self {
+
+        let first = match points.first() {
+            Some(first) => Point3::from(*first),
+            None => return Self::empty()
+        };
+        let farthest = |from: Point3<f32>| {
+            points.iter().map(|p| Point3::from(*p)).fold(from, |best, p| {
+                if (p - from).magnitude2() > (best - from).magnitude2() { p } else { best }
+            })
+        };
+        let a = farthest(first);
+        let b = farthest(a);
+        let mut center = a.midpoint(b);
+        let mut radius = (b - a).magnitude() * 0.5;
+        for point in points {
+            let point = Point3::from(*point);
+            let distance = (point - center).magnitude();
+            if distance > radius {
+                // moves the far side of the sphere out to the point, keeping the near side in place
+                radius = (radius + distance) * 0.5;
+                center = point - (point - center) * (radius / distance);
+            }
+        }
+        Self { center: center.into(), radius }
+    }
+
+    // the smallest sphere holding both spheres
+    pub fn union(&self, other: &BoundingSphere) -> BoundingSphere {
+
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        let offset = Point3::from(other.center) - Point3::from(self.center);
+        let distance = offset.magnitude();
+        if distance + other.radius <= self.radius {
+            return *self;
+        }
+        if distance + self.radius <= other.radius {
+            return *other;
+        }
+        let radius = (distance + self.radius + other.radius) * 0.5;
+        let center = Point3::from(self.center) + offset * ((radius - self.radius) / distance);
+        BoundingSphere { center: center.into(), radius }
+    }
+
+    // scales the radius by the largest scale of the matrix, so the sphere still holds everything
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> BoundingSphere {
+
+        if self.is_empty() {
+            return *self;
+        }
+        let scale = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()]
+            .iter()
+            .map(|axis| matrix.transform_vector(*axis).magnitude())
+            .fold(0.0, f32::max);
+        BoundingSphere {
+            center: matrix.transform_point(Point3::from(self.center)).into(),
+            radius: self.radius * scale
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sphere_holds_every_point() {
+
+        let points = [[0.0, 0.0, 0.0], [4.0, 0.0, 0.0], [2.0, 3.0, 0.0], [2.0, -1.0, 1.0], [1.0, 1.0, -2.0]];
+        let sphere = BoundingSphere::from_points(&points);
+        for point in &points {
+            let distance = (Point3::from(*point) - Point3::from(sphere.center)).magnitude();
+            assert!(distance <= sphere.radius + 1e-5, "{:?} outside {:?}", point, sphere);
+        }
+        // never looser than the sphere around the box
+        assert!(sphere.radius <= Aabb::from_points(&points).bounding_sphere().radius);
+    }
+
+    #[test]
+    fn union_of_spheres_holds_both() {
+
+        let a = BoundingSphere { center: [0.0, 0.0, 0.0], radius: 1.0 };
+        let b = BoundingSphere { center: [4.0, 0.0, 0.0], radius: 1.0 };
+        assert_eq!(a.union(&b), BoundingSphere { center: [2.0, 0.0, 0.0], radius: 3.0 });
+        let inside = BoundingSphere { center: [0.5, 0.0, 0.0], radius: 0.2 };
+        assert_eq!(a.union(&inside), a);
+        assert_eq!(BoundingSphere::empty().union(&a), a);
+    }
+
+    #[test]
+    fn transformed_bounds_follow_the_matrix() {
+
+        let bounds = Aabb { min: [-1.0, -1.0, -1.0], max: [1.0, 1.0, 1.0] };
+        let matrix = Matrix4::from_translation(Vector3::new(10.0, 0.0, 0.0)) * Matrix4::from_nonuniform_scale(2.0, 1.0, 1.0);
+        assert_eq!(bounds.transform(&matrix), Aabb { min: [8.0, -1.0, -1.0], max: [12.0, 1.0, 1.0] });
+
+        let sphere = bounds.bounding_sphere().transform(&matrix);
+        assert_eq!(sphere.center, [10.0, 0.0, 0.0]);
+        assert!((sphere.radius - 2.0 * 3f32.sqrt()).abs() < 1e-5);
+        assert!(Aabb::empty().union(&bounds) == bounds && Aabb::empty().transform(&matrix).is_empty());
+    }
+}
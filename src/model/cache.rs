@@ -0,0 +1,653 @@
+use std::{fs, io, path::{Path, PathBuf}, sync::atomic::{AtomicU64, Ordering}, time::UNIX_EPOCH};
+
+use super::bounds::Aabb;
+use super::error::{LoadReport, ObjError, ParseMode};
+use super::mtl::{MaterialData, TextureMap};
+use super::weld::WeldReport;
+use super::{LoadOptions, MaterialRange, MeshData, ScalarProperty, SceneData, Submesh};
+use crate::instance::Instance;
+
+const MAGIC: &[u8; 4] = b"AGRC";
+// bumped whenever the layout below or the way meshes are built changes
+const VERSION: u32 = 5;
+const EXTENSION: &str = "mesh";
+
+// 64 bit FNV-1a, enough to tell whether a file has changed
+struct Fnv(u64);
+
+impl Fnv {
+
+    fn new() -> Self {
+        Fnv(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+fn content_hash(bytes: &[u8]) -> u64 {
+    let mut hash = Fnv::new();
+    hash.write(bytes);
+    hash.0
+}
+
+// size and modification time of a file, which decide whether it has to be hashed again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    size: u64,
+    modified: (u64, u32)
+}
+
+impl Stamp {
+
+    fn of(path: &Path) -> io::Result<Self> {
+
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Ok(Self {
+            size: metadata.len(),
+            modified: (modified.as_secs(), modified.subsec_nanos())
+        })
+    }
+}
+
+// everything needed to tell whether a cache file still matches its source
+#[derive(Clone)]
+struct Header {
+    source: String,
+    stamp: Stamp,
+    hash: u64,
+    // material libraries, which are only compared by their stamps. ones that were missing
+    // have none, so the entry goes stale once they are added
+    libraries: Vec<(PathBuf, Option<Stamp>)>,
+    weld: WeldReport,
+    // the warnings of the load that was cached, as they were shown
+    warnings: Vec<String>
+}
+
+#[derive(Default)]
+struct Encoder {
+    bytes: Vec<u8>
+}
+
+impl Encoder {
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn str(&mut self, value: &str) {
+        self.u64(value.len() as u64);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    // arrays are stored as they are in memory, caches aren't meant to move between machines
+    fn slice<T: bytemuck::Pod>(&mut self, values: &[T]) {
+        self.u64(values.len() as u64);
+        self.bytes.extend_from_slice(bytemuck::cast_slice(values));
+    }
+
+    fn stamp(&mut self, stamp: &Stamp) {
+        self.u64(stamp.size);
+        self.u64(stamp.modified.0);
+        self.u32(stamp.modified.1);
+    }
+
+    fn header(&mut self, header: &Header) {
+
+        self.bytes.extend_from_slice(MAGIC);
+        self.u32(VERSION);
+        self.str(&header.source);
+        self.stamp(&header.stamp);
+        self.u64(header.hash);
+        self.u64(header.libraries.len() as u64);
+        for (path, stamp) in &header.libraries {
+            self.str(&path.to_string_lossy());
+            self.u32(stamp.is_some() as u32);
+            self.stamp(&stamp.unwrap_or(Stamp { size: 0, modified: (0, 0) }));
+        }
+        self.u64(header.weld.merged as u64);
+        self.u64(header.weld.unreferenced as u64);
+        self.u64(header.warnings.len() as u64);
+        for warning in &header.warnings {
+            self.str(warning);
+        }
+    }
+
+    fn mesh(&mut self, mesh: &MeshData) {
+
+        self.slice(&mesh.positions);
+        self.slice(&mesh.normals);
+        self.slice(&mesh.texcoords);
+        self.slice(&mesh.colors);
+        self.slice(&mesh.tangents);
+        self.slice(&mesh.indices);
+        self.slice(&mesh.line_indices);
+        self.slice(&mesh.point_indices);
+        self.u64(mesh.scalars.len() as u64);
+        for scalar in &mesh.scalars {
+            self.str(&scalar.name);
+            self.slice(&scalar.values);
+        }
+
+        self.u64(mesh.submeshes.len() as u64);
+        for submesh in &mesh.submeshes {
+            self.str(&submesh.name);
+            self.slice(&[submesh.indices.start, submesh.indices.end]);
+            self.u64(submesh.material_ranges.len() as u64);
+            for range in &submesh.material_ranges {
+                self.slice(&[range.material as u32, range.indices.start, range.indices.end]);
+            }
+            self.slice(&[submesh.bounds.min, submesh.bounds.max]);
+        }
+
+        self.u64(mesh.materials.len() as u64);
+        for material in &mesh.materials {
+            self.str(&material.name);
+            self.slice(&[material.ambient, material.diffuse, material.specular]);
+            self.f32(material.shininess);
+            self.f32(material.dissolve);
+            self.u32(material.illumination);
+            self.u64(material.maps.len() as u64);
+            for (map, path) in &material.maps {
+                self.str(map.keyword());
+                self.str(&path.to_string_lossy());
+            }
+            self.u64(material.embedded.len() as u64);
+            for (map, image) in &material.embedded {
+                self.str(map.keyword());
+                self.slice(image);
+            }
+        }
+    }
+
+    fn scene(&mut self, scene: &SceneData) {
+
+        self.u64(scene.meshes.len() as u64);
+        for mesh in &scene.meshes {
+            self.mesh(mesh);
+        }
+        self.u32(scene.instances.is_some() as u32);
+        for instances in scene.instances.iter().flatten() {
+            let instances = instances.iter().map(|instance| {
+                let (position, rotation, scaling) = (instance.position, instance.rotation, instance.scaling);
+                [position.x, position.y, position.z, rotation.s, rotation.v.x, rotation.v.y, rotation.v.z, scaling.x, scaling.y, scaling.z]
+            }).collect::<Vec<[f32; 10]>>();
+            self.slice(&instances);
+        }
+    }
+}
+
+// reads back what Encoder wrote, None means the file is truncated or not a cache at all
+struct Decoder<'a> {
+    bytes: &'a [u8]
+}
+
+impl<'a> Decoder<'a> {
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+
+        if len > self.bytes.len() {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn len(&mut self) -> Option<usize> {
+        usize::try_from(self.u64()?).ok()
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        Some(f32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn str(&mut self) -> Option<String> {
+        let len = self.len()?;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    fn slice<T: bytemuck::Pod>(&mut self) -> Option<Vec<T>> {
+        let len = self.len()?;
+        let bytes = self.take(len.checked_mul(std::mem::size_of::<T>())?)?;
+        // copied rather than cast, the file contents aren't aligned for T
+        let mut values = vec![T::zeroed(); len];
+        bytemuck::cast_slice_mut::<T, u8>(&mut values).copy_from_slice(bytes);
+        Some(values)
+    }
+
+    fn array<T: bytemuck::Pod, const N: usize>(&mut self) -> Option<[T; N]> {
+        self.slice::<T>()?.try_into().ok()
+    }
+
+    fn stamp(&mut self) -> Option<Stamp> {
+        Some(Stamp {
+            size: self.u64()?,
+            modified: (self.u64()?, self.u32()?)
+        })
+    }
+
+    fn header(&mut self) -> Option<Header> {
+
+        if self.take(MAGIC.len())? != MAGIC || self.u32()? != VERSION {
+            return None;
+        }
+        let source = self.str()?;
+        let stamp = self.stamp()?;
+        let hash = self.u64()?;
+        let libraries = (0..self.len()?).map(|_| {
+            let path = PathBuf::from(self.str()?);
+            let found = self.u32()? != 0;
+            let stamp = self.stamp()?;
+            Some((path, Some(stamp).filter(|_| found)))
+        }).collect::<Option<Vec<_>>>()?;
+        let weld = WeldReport {
+            merged: self.len()?,
+            unreferenced: self.len()?
+        };
+        let warnings = (0..self.len()?).map(|_| self.str()).collect::<Option<Vec<_>>>()?;
+        Some(Header { source, stamp, hash, libraries, weld, warnings })
+    }
+
+    fn mesh(&mut self) -> Option<MeshData> {
+
+        let positions = self.slice()?;
+        let normals = self.slice()?;
+        let texcoords = self.slice()?;
+        let colors = self.slice()?;
+        let tangents = self.slice()?;
+        let indices = self.slice()?;
+        let line_indices = self.slice()?;
+        let point_indices = self.slice()?;
+        let scalars = (0..self.len()?).map(|_| {
+            Some(ScalarProperty { name: self.str()?, values: self.slice()? })
+        }).collect::<Option<Vec<_>>>()?;
+
+        let submeshes = (0..self.len()?).map(|_| {
+            let name = self.str()?;
+            let [start, end] = self.array::<u32, 2>()?;
+            let material_ranges = (0..self.len()?).map(|_| {
+                let [material, start, end] = self.array::<u32, 3>()?;
+                Some(MaterialRange { material: material as usize, indices: start..end })
+            }).collect::<Option<Vec<_>>>()?;
+            let [min, max] = self.array::<[f32; 3], 2>()?;
+            Some(Submesh { name, indices: start..end, material_ranges, bounds: Aabb { min, max } })
+        }).collect::<Option<Vec<_>>>()?;
+
+        let materials = (0..self.len()?).map(|_| {
+            let mut material = MaterialData::new(&self.str()?);
+            [material.ambient, material.diffuse, material.specular] = self.array::<[f32; 3], 3>()?;
+            material.shininess = self.f32()?;
+            material.dissolve = self.f32()?;
+            material.illumination = self.u32()?;
+            for _ in 0..self.len()? {
+                let map = TextureMap::from_keyword(&self.str()?)?;
+                material.maps.insert(map, PathBuf::from(self.str()?));
+            }
+            for _ in 0..self.len()? {
+                let map = TextureMap::from_keyword(&self.str()?)?;
+                material.embedded.insert(map, self.slice()?);
+            }
+            Some(material)
+        }).collect::<Option<Vec<_>>>()?;
+
+        Some(MeshData { positions, normals, texcoords, colors, tangents, scalars, indices, line_indices, point_indices, submeshes, materials })
+    }
+
+    fn scene(&mut self) -> Option<SceneData> {
+
+        let meshes = (0..self.len()?).map(|_| self.mesh()).collect::<Option<Vec<_>>>()?;
+        let instances = match self.u32()? {
+            0 => None,
+            _ => Some(meshes.iter().map(|_| {
+                let instances = self.slice::<[f32; 10]>()?.into_iter().map(|[x, y, z, s, i, j, k, sx, sy, sz]| Instance {
+                    position: cgmath::Vector3::new(x, y, z),
+                    rotation: cgmath::Quaternion::new(s, i, j, k),
+                    scaling: cgmath::Vector3::new(sx, sy, sz)
+                }).collect();
+                Some(instances)
+            }).collect::<Option<Vec<_>>>()?)
+        };
+        Some(SceneData { meshes, instances })
+    }
+}
+
+// a directory of parsed meshes, so unchanged files don't have to be parsed again
+pub struct MeshCache {
+    dir: PathBuf
+}
+
+impl MeshCache {
+
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into()
+        }
+    }
+
+    // $XDG_CACHE_HOME/agr, or ~/.cache/agr
+    pub fn default_dir() -> PathBuf {
+
+        let base = std::env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()).map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+            .unwrap_or_else(std::env::temp_dir);
+        base.join("agr")
+    }
+
+    pub fn get_dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // every source file gets its own cache file for each set of options it was loaded with
+    fn entry(&self, source: &str, options: &LoadOptions) -> PathBuf {
+
+        let mut hash = Fnv::new();
+        hash.write(source.as_bytes());
+        hash.write(&[match options.mode { ParseMode::Strict => 0, ParseMode::Lenient => 1 }]);
+        hash.write(&options.crease_angle.0.to_le_bytes());
+        match options.weld_epsilon {
+            Some(epsilon) => hash.write(&epsilon.to_le_bytes()),
+            None => hash.write(b"none")
+        }
+        // other importers may read the same file differently, and other file systems give
+        // its material libraries other contents
+        for importer in options.importers.get_importers() {
+            hash.write(importer.name().as_bytes());
+        }
+        hash.write(options.files.id().as_bytes());
+        self.dir.join(format!("{:016x}.{}", hash.0, EXTENSION))
+    }
+
+    // loads a scene from the cache when its source and material libraries are unchanged,
+    // otherwise parses it and stores the result. failing to write the cache isn't an error,
+    // the scene is returned all the same. `progress` is only called while parsing
+    pub fn load_scene(&self, filename: &str, options: &LoadOptions, progress: &(dyn Fn(f32) + Sync)) -> Result<(SceneData, LoadReport), ObjError> {
+
+        let io_error = |source| ObjError::Io { file: filename.to_string(), source };
+        let source = fs::canonicalize(filename).map_err(io_error)?.to_string_lossy().to_string();
+        let stamp = Stamp::of(Path::new(filename)).map_err(io_error)?;
+        let entry = self.entry(&source, options);
+
+        let cached = fs::read(&entry).ok();
+        let mut decoder = cached.as_deref().map(|bytes| Decoder { bytes });
+        let header = decoder.as_mut().and_then(Decoder::header)
+            .filter(|header| header.source == source && header.stamp.size == stamp.size)
+            .filter(|header| header.libraries.iter().all(|(path, stamp)| Stamp::of(path).ok() == *stamp));
+
+        super::with_file_bytes(filename, |bytes| {
+            if let (Some(header), Some(decoder)) = (&header, decoder.as_mut()) {
+                // a file that was only touched is recognised by its contents
+                let unchanged = header.stamp == stamp || header.hash == content_hash(bytes);
+                if let Some(scene) = if unchanged { decoder.scene() } else { None } {
+                    if header.stamp != stamp {
+                        self.store(&entry, &Header { stamp, ..header.clone() }, &scene);
+                    }
+                    let report = LoadReport {
+                        warnings: header.warnings.iter().cloned().map(ObjError::Cached).collect(),
+                        weld: header.weld,
+                        from_cache: true,
+                        ..LoadReport::default()
+                    };
+                    return Ok((scene, report));
+                }
+            }
+
+            let (scene, report) = SceneData::from_bytes(bytes, filename, options, progress)?;
+            let libraries = report.libraries.iter().map(|path| {
+                let absolute = fs::canonicalize(path).or_else(|_| std::path::absolute(path)).unwrap_or_else(|_| path.clone());
+                (absolute, Stamp::of(path).ok())
+            }).collect();
+            let header = Header {
+                source: source.clone(),
+                stamp,
+                hash: content_hash(bytes),
+                libraries,
+                weld: report.weld,
+                warnings: report.warnings.iter().map(ToString::to_string).collect()
+            };
+            self.store(&entry, &header, &scene);
+            Ok((scene, report))
+        })
+    }
+
+    // `load_scene` with the scene as one mesh
+    pub fn load(&self, filename: &str, options: &LoadOptions) -> Result<(MeshData, LoadReport), ObjError> {
+
+        let (scene, report) = self.load_scene(filename, options, &|_| ())?;
+        Ok((scene.into_mesh(), report))
+    }
+
+    fn store(&self, entry: &Path, header: &Header, scene: &SceneData) {
+
+        let mut encoder = Encoder::default();
+        encoder.header(header);
+        encoder.scene(scene);
+        // written next to the entry and renamed, so a crash never leaves half a cache behind.
+        // the name is unique to the process and the write, so concurrent loads don't collide
+        static WRITES: AtomicU64 = AtomicU64::new(0);
+        let temporary = entry.with_extension(format!("{}.{}.tmp", std::process::id(), WRITES.fetch_add(1, Ordering::Relaxed)));
+        let result = fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(&temporary, &encoder.bytes))
+            .and_then(|_| fs::rename(&temporary, entry));
+        if let Err(err) = result {
+            log::warn!("could not write mesh cache {}: {}", entry.display(), err);
+        }
+    }
+
+    // removes every cached mesh, returning how many there were
+    pub fn purge(&self) -> io::Result<usize> {
+
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err)
+        };
+        let mut removed = 0;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == EXTENSION) {
+                fs::remove_file(path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, time::{Duration, SystemTime}};
+
+    use super::*;
+    use crate::model::ZipFiles;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("agr_cache_{}_{}", name, std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn file(&self, name: &str, contents: &str) -> String {
+            let path = self.0.join(name);
+            fs::write(&path, contents).unwrap();
+            path.to_string_lossy().to_string()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    const SOURCE: &str = "mtllib box.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0 1 0 0\nv 0 1 0\nvt 0 0\ng side\nusemtl red\nf 1/1 2/1 3/1 4/1\nl 1 3\np 2\n";
+    const LIBRARY: &str = "newmtl red\nKd 1 0 0\nmap_Kd red.png\n";
+
+    #[test]
+    fn cached_mesh_is_the_parsed_one() {
+
+        let dir = TempDir::new("round_trip");
+        dir.file("box.mtl", LIBRARY);
+        let source = dir.file("box.obj", SOURCE);
+        let cache = MeshCache::new(dir.0.join("cache"));
+        let options = LoadOptions::default();
+
+        let (parsed, report) = cache.load(&source, &options).unwrap();
+        assert!(!report.from_cache);
+        let (cached, report) = cache.load(&source, &options).unwrap();
+        assert!(report.from_cache);
+        assert_eq!(cached, parsed);
+        assert_eq!(cached, MeshData::from_obj_file(&source, &options).unwrap().0);
+    }
+
+    #[test]
+    fn cached_scene_keeps_its_instances() {
+
+        let dir = TempDir::new("scene");
+        let source = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/cubes.glb");
+        let cache = MeshCache::new(dir.0.join("cache"));
+        let options = LoadOptions::default();
+
+        let (parsed, _) = cache.load_scene(source, &options, &|_| ()).unwrap();
+        let (cached, report) = cache.load_scene(source, &options, &|_| ()).unwrap();
+        assert!(report.from_cache);
+        assert_eq!(cached.instances.as_ref().map(|instances| instances[0].len()), Some(2));
+        assert_eq!(cached, parsed);
+    }
+
+    #[test]
+    fn stale_entries_are_rebuilt() {
+
+        let dir = TempDir::new("stale");
+        let library = dir.file("box.mtl", LIBRARY);
+        let source = dir.file("box.obj", SOURCE);
+        let cache = MeshCache::new(dir.0.join("cache"));
+        let options = LoadOptions::default();
+        let from_cache = |options: &LoadOptions| cache.load(&source, options).unwrap().1.from_cache;
+
+        assert!(!from_cache(&options));
+        assert!(from_cache(&options));
+
+        // other options are cached separately
+        let unwelded = LoadOptions { weld_epsilon: None, ..LoadOptions::default() };
+        assert!(!from_cache(&unwelded));
+        assert!(from_cache(&options));
+
+        // touching the file keeps the entry, since the contents are the same
+        let later = SystemTime::now() + Duration::from_secs(10);
+        File::options().write(true).open(&source).unwrap().set_modified(later).unwrap();
+        assert!(from_cache(&options));
+
+        dir.file("box.obj", &SOURCE.replace("v 0 1 0", "v 0 2 0"));
+        assert!(!from_cache(&options));
+        assert!(from_cache(&options));
+
+        File::options().write(true).open(&library).unwrap().set_modified(later + Duration::from_secs(10)).unwrap();
+        assert!(!from_cache(&options));
+
+        assert_eq!(cache.purge().unwrap(), 2);
+        assert!(!from_cache(&options));
+    }
+
+    #[test]
+    fn warnings_are_kept_and_missing_libraries_are_watched() {
+
+        let dir = TempDir::new("warnings");
+        let source = dir.file("box.obj", SOURCE);
+        let cache = MeshCache::new(dir.0.join("cache"));
+        let options = LoadOptions { mode: ParseMode::Lenient, ..LoadOptions::default() };
+        let warnings = |report: &LoadReport| report.warnings.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        // without its library the model loads with warnings, which a cache hit repeats
+        let (_, parsed) = cache.load(&source, &options).unwrap();
+        assert!(!parsed.warnings.is_empty());
+        let (_, cached) = cache.load(&source, &options).unwrap();
+        assert!(cached.from_cache);
+        assert_eq!(warnings(&cached), warnings(&parsed));
+
+        dir.file("box.mtl", LIBRARY);
+        let (mesh, report) = cache.load(&source, &options).unwrap();
+        assert!(!report.from_cache);
+        assert!(report.warnings.is_empty());
+        assert_eq!(mesh.materials.iter().filter(|material| material.name == "red").count(), 1);
+        assert!(cache.load(&source, &options).unwrap().1.from_cache);
+    }
+
+    #[test]
+    fn file_systems_are_cached_separately() {
+
+        let dir = TempDir::new("file_systems");
+        let source = dir.file("box.obj", SOURCE);
+        let cache = MeshCache::new(dir.0.join("cache"));
+        let local = LoadOptions::default();
+        let bundle = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/bundle.zip")).unwrap();
+        let zip = LoadOptions { files: std::sync::Arc::new(ZipFiles::from_reader(bundle, "bundle.zip").unwrap()), ..LoadOptions::default() };
+
+        let source_key = fs::canonicalize(&source).unwrap().to_string_lossy().to_string();
+        assert_ne!(cache.entry(&source_key, &local), cache.entry(&source_key, &zip));
+        assert!(!cache.load(&source, &local).unwrap().1.from_cache);
+        assert!(!cache.load(&source, &zip).unwrap().1.from_cache);
+        assert!(cache.load(&source, &local).unwrap().1.from_cache);
+    }
+
+    #[test]
+    fn truncated_entries_are_ignored() {
+
+        let dir = TempDir::new("truncated");
+        dir.file("box.mtl", LIBRARY);
+        let source = dir.file("box.obj", SOURCE);
+        let cache = MeshCache::new(dir.0.join("cache"));
+        let options = LoadOptions::default();
+
+        let (parsed, _) = cache.load(&source, &options).unwrap();
+        let entry = cache.entry(&fs::canonicalize(&source).unwrap().to_string_lossy(), &options);
+        let bytes = fs::read(&entry).unwrap();
+        fs::write(&entry, &bytes[..bytes.len() - 5]).unwrap();
+
+        let (loaded, report) = cache.load(&source, &options).unwrap();
+        assert!(!report.from_cache);
+        assert_eq!(loaded, parsed);
+    }
+
+    #[test]
+    fn progress_is_reported_while_parsing_only() {
+
+        let dir = TempDir::new("progress");
+        dir.file("box.mtl", LIBRARY);
+        let source = dir.file("box.obj", SOURCE);
+        let cache = MeshCache::new(dir.0.join("cache"));
+        let options = LoadOptions::default();
+        let reported = std::sync::Mutex::new(Vec::new());
+        let progress = |fraction: f32| reported.lock().unwrap().push(fraction);
+
+        cache.load_scene(&source, &options, &progress).unwrap();
+        assert_eq!(reported.lock().unwrap().last(), Some(&1.0));
+        reported.lock().unwrap().clear();
+        cache.load_scene(&source, &options, &progress).unwrap();
+        assert!(reported.lock().unwrap().is_empty());
+    }
+}
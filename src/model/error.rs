@@ -0,0 +1,100 @@
+use std::{fmt, io, path::PathBuf};
+
+use super::weld::WeldReport;
+
+// where in a file something went wrong, along with the text that caused it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub text: String
+}
+
+impl fmt::Display for Location {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io { file: String, source: std::io::Error },
+    BadNumber(Location),
+    ZeroIndex(Location),
+    IndexOutOfRange { location: Location, count: usize },
+    WrongComponentCount { location: Location, expected: &'static str },
+    TooFewVertices(Location),
+    UnknownMaterial(Location),
+    UnsupportedDirective(Location),
+    // a warning of an earlier load, kept in the mesh cache as it was shown
+    Cached(String)
+}
+
+impl fmt::Display for ObjError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { file, source } => write!(f, "{}: {}", file, source),
+            ObjError::BadNumber(location) => write!(f, "{}: bad number `{}`", location, location.text),
+            ObjError::ZeroIndex(location) => write!(f, "{}: index `{}` is zero, OBJ indices start at 1", location, location.text),
+            ObjError::IndexOutOfRange { location, count } => write!(f, "{}: index `{}` is out of range, only {} defined so far", location, location.text, count),
+            ObjError::WrongComponentCount { location, expected } => write!(f, "{}: expected {} components in `{}`", location, expected, location.text),
+            ObjError::TooFewVertices(location) => write!(f, "{}: face `{}` has fewer than 3 vertices", location, location.text),
+            ObjError::UnknownMaterial(location) => write!(f, "{}: material `{}` is not defined in any loaded library", location, location.text),
+            ObjError::UnsupportedDirective(location) => write!(f, "{}: unsupported directive `{}`", location, location.text),
+            ObjError::Cached(warning) => write!(f, "{}", warning)
+        }
+    }
+}
+
+impl ObjError {
+
+    // a file that can be read but doesn't hold what its format says it should
+    pub fn invalid_data(filename: &str, message: String) -> Self {
+        ObjError::Io { file: filename.to_string(), source: io::Error::new(io::ErrorKind::InvalidData, message) }
+    }
+}
+
+impl std::error::Error for ObjError {
+
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    // the first malformed line aborts the load
+    Strict,
+    // malformed lines are skipped and collected in the report
+    Lenient
+}
+
+impl ParseMode {
+
+    // fails a strict load, and is only reported by a lenient one
+    pub fn problem(self, err: ObjError, report: &mut LoadReport) -> Result<(), ObjError> {
+        match self {
+            ParseMode::Strict => Err(err),
+            ParseMode::Lenient => {
+                report.warnings.push(err);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    // a cached load has those of the load that was cached, as `ObjError::Cached`
+    pub warnings: Vec<ObjError>,
+    pub weld: WeldReport,
+    // material libraries the model was read from, or tried to be
+    pub libraries: Vec<PathBuf>,
+    pub from_cache: bool
+}
@@ -0,0 +1,281 @@
+use std::{borrow::Cow, io::{self, Write}, path::{Component, Path, PathBuf}};
+
+use super::files::normalize;
+use super::mtl::MaterialData;
+use super::MeshData;
+
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    // digits written after the decimal point
+    pub precision: usize,
+    // writes the normals, otherwise they are generated again when the file is read
+    pub normals: bool,
+    // writes each submesh as a `g` group, otherwise all faces end up in one
+    pub groups: bool,
+    // writes `usemtl` statements and a material library next to the file
+    pub materials: bool
+}
+
+impl Default for ExportOptions {
+
+    fn default() -> Self {
+        Self {
+            precision: 6,
+            normals: true,
+            groups: true,
+            materials: true
+        }
+    }
+}
+
+fn write_values<W: Write>(writer: &mut W, keyword: &str, values: &[f32], precision: usize) -> io::Result<()> {
+
+    write!(writer, "{}", keyword)?;
+    for value in values {
+        write!(writer, " {:.*}", precision, value)?;
+    }
+    writeln!(writer)
+}
+
+// names are read up to the first space by many programs, and `mtllib` takes several files,
+// so spaces and tabs in the names written become underscores
+pub(super) fn escape(name: &str) -> Cow<'_, str> {
+
+    if name.contains(char::is_whitespace) {
+        Cow::Owned(name.replace(char::is_whitespace, "_"))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+// `path` as seen from `dir`, going up with `..` where needed. paths are resolved against the
+// working directory first, and written absolute when they have no root in common
+fn relative_to(path: &Path, dir: &Path) -> PathBuf {
+
+    // an empty path is the working directory itself
+    let absolute = |path: &Path| {
+        let path = if path.as_os_str().is_empty() { Path::new(".") } else { path };
+        std::path::absolute(path).map(|path| normalize(&path)).unwrap_or_else(|_| path.to_path_buf())
+    };
+    let (path, dir) = (absolute(path), absolute(dir));
+    let (mut path_parts, mut dir_parts) = (path.components().peekable(), dir.components().peekable());
+    if path_parts.peek() != dir_parts.peek() {
+        return path;
+    }
+    while path_parts.peek().is_some() && path_parts.peek() == dir_parts.peek() {
+        path_parts.next();
+        dir_parts.next();
+    }
+    dir_parts.map(|_| Component::ParentDir).chain(path_parts).collect()
+}
+
+// every vertex has all of its attributes at the same index, so a corner is written as i, i/i or i/i/i
+fn write_corner<W: Write>(writer: &mut W, index: u32, texcoords: bool, normals: bool) -> io::Result<()> {
+
+    let index = index + 1;
+    match (texcoords, normals) {
+        (false, false) => write!(writer, " {}", index),
+        (true, false) => write!(writer, " {}/{}", index, index),
+        (false, true) => write!(writer, " {}//{}", index, index),
+        (true, true) => write!(writer, " {}/{}/{}", index, index, index)
+    }
+}
+
+// writes the mesh as OBJ, `mtllib` is the name of the material library to refer to, if any.
+// materials are only written when there is one
+pub fn write_obj<W: Write>(mesh: &MeshData, mut writer: W, options: &ExportOptions, mtllib: Option<&str>) -> io::Result<()> {
+
+    // without a library `usemtl` would name materials that can't be found
+    let mtllib = mtllib.filter(|_| options.materials);
+    if let Some(mtllib) = mtllib {
+        writeln!(writer, "mtllib {}", escape(mtllib))?;
+    }
+
+    for (i, position) in mesh.positions.iter().enumerate() {
+        match mesh.colors.get(i) {
+            Some(color) => write_values(&mut writer, "v", &[&position[..], &color[..]].concat(), options.precision)?,
+            None => write_values(&mut writer, "v", position, options.precision)?
+        }
+    }
+    for texcoord in &mesh.texcoords {
+        write_values(&mut writer, "vt", texcoord, options.precision)?;
+    }
+    if options.normals {
+        for normal in &mesh.normals {
+            write_values(&mut writer, "vn", normal, options.precision)?;
+        }
+    }
+
+    let texcoords = !mesh.texcoords.is_empty();
+    for submesh in &mesh.submeshes {
+        if options.groups {
+            writeln!(writer, "g {}", escape(&submesh.name))?;
+        }
+        for range in &submesh.material_ranges {
+            if mtllib.is_some() {
+                writeln!(writer, "usemtl {}", escape(&mesh.materials[range.material].name))?;
+            }
+            for triangle in mesh.indices[range.indices.start as usize..range.indices.end as usize].chunks(3) {
+                write!(writer, "f")?;
+                for &index in triangle {
+                    write_corner(&mut writer, index, texcoords, options.normals)?;
+                }
+                writeln!(writer)?;
+            }
+        }
+    }
+
+    for segment in mesh.line_indices.chunks(2) {
+        writeln!(writer, "l {} {}", segment[0] + 1, segment[1] + 1)?;
+    }
+    for &point in &mesh.point_indices {
+        writeln!(writer, "p {}", point + 1)?;
+    }
+    writer.flush()
+}
+
+// texture paths are written relative to `dir`, the directory the library is saved in
+pub fn write_mtl<W: Write>(materials: &[MaterialData], mut writer: W, options: &ExportOptions, dir: &Path) -> io::Result<()> {
+
+    for (i, material) in materials.iter().enumerate() {
+        if i > 0 {
+            writeln!(writer)?;
+        }
+        writeln!(writer, "newmtl {}", escape(&material.name))?;
+        write_values(&mut writer, "Ka", &material.ambient, options.precision)?;
+        write_values(&mut writer, "Kd", &material.diffuse, options.precision)?;
+        write_values(&mut writer, "Ks", &material.specular, options.precision)?;
+        write_values(&mut writer, "Ns", &[material.shininess], options.precision)?;
+        write_values(&mut writer, "d", &[material.dissolve], options.precision)?;
+        writeln!(writer, "illum {}", material.illumination)?;
+
+        // sorted, so exporting the same materials always gives the same file. embedded images
+        // have no file for the library to name, so they are left out
+        let mut maps = material.maps.iter().collect::<Vec<_>>();
+        maps.sort_by_key(|(map, _)| map.keyword());
+        for (map, path) in maps {
+            writeln!(writer, "{} {}", map.keyword(), relative_to(path, dir).display())?;
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{LoadOptions, ParseMode, TextureMap};
+
+    fn strict() -> LoadOptions {
+        LoadOptions {
+            mode: ParseMode::Strict,
+            ..LoadOptions::default()
+        }
+    }
+
+    // position and normal of every triangle corner, in drawing order
+    fn corners(mesh: &MeshData) -> Vec<[f32; 6]> {
+        mesh.indices.iter().map(|&i| {
+            let ([x, y, z], [nx, ny, nz]) = (mesh.positions[i as usize], mesh.normals[i as usize]);
+            [x, y, z, nx, ny, nz]
+        }).collect()
+    }
+
+    fn assert_same_geometry(a: &MeshData, b: &MeshData) {
+
+        let (a, b) = (corners(a), corners(b));
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert!(a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-5), "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn round_trip_keeps_geometry() {
+
+        let options = strict();
+        let (original, _) = MeshData::from_obj_file(concat!(env!("CARGO_MANIFEST_DIR"), "/src/teapot.obj"), &options).unwrap();
+        let mut written = Vec::new();
+        original.write_obj(&mut written, &ExportOptions::default(), None).unwrap();
+        let (parsed, _) = MeshData::from_obj(&written[..], "teapot.obj", &options).unwrap();
+
+        assert_same_geometry(&original, &parsed);
+        assert_eq!(parsed.positions.len(), original.positions.len());
+    }
+
+    #[test]
+    fn round_trip_through_files_keeps_groups_and_materials() {
+
+        let dir = std::env::temp_dir().join(format!("agr_export_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("input.mtl"), "newmtl red\nKd 1 0 0\nnewmtl blue\nKd 0 0 1\nNs 10\n").unwrap();
+        std::fs::write(dir.join("input.obj"), "\
+            mtllib input.mtl\n\
+            v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0 0 1\n\
+            g front\nusemtl red\nf 1 2 3 4\n\
+            g back\nusemtl blue\nf 1 5 2\nusemtl red\nf 1 4 5\n\
+            l 1 3\n").unwrap();
+
+        let options = strict();
+        let input = dir.join("input.obj").to_string_lossy().to_string();
+        let output = dir.join("output.obj").to_string_lossy().to_string();
+        let (original, _) = MeshData::from_obj_file(&input, &options).unwrap();
+        original.save_obj(&output, &ExportOptions::default()).unwrap();
+        let (parsed, _) = MeshData::from_obj_file(&output, &options).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_same_geometry(&original, &parsed);
+        assert_eq!(parsed.submeshes, original.submeshes);
+        assert_eq!(parsed.materials, original.materials);
+        assert_eq!(parsed.line_indices.len(), 2);
+    }
+
+    #[test]
+    fn textures_are_found_from_a_library_saved_elsewhere() {
+
+        let dir = std::env::temp_dir().join(format!("agr_export_elsewhere_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("in/textures")).unwrap();
+        std::fs::create_dir_all(dir.join("out/my model")).unwrap();
+        std::fs::write(dir.join("in/textures/wood.png"), b"").unwrap();
+        std::fs::write(dir.join("in/input.mtl"), "newmtl oak wood\nmap_Kd textures/wood.png\n").unwrap();
+        std::fs::write(dir.join("in/input.obj"), "mtllib input.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\ng front side\nusemtl oak wood\nf 1 2 3\n").unwrap();
+
+        let options = strict();
+        let output = dir.join("out/my model/tree house.obj").to_string_lossy().to_string();
+        let (original, _) = MeshData::from_obj_file(&dir.join("in/input.obj").to_string_lossy(), &options).unwrap();
+        original.save_obj(&output, &ExportOptions::default()).unwrap();
+        let written = std::fs::read_to_string(&output).unwrap();
+        let library = std::fs::read_to_string(dir.join("out/my model/tree_house.mtl")).unwrap();
+        let (parsed, _) = MeshData::from_obj_file(&output, &options).unwrap();
+        let texture = std::fs::canonicalize(&parsed.materials[0].maps[&TextureMap::Diffuse]).ok();
+        let expected = std::fs::canonicalize(dir.join("in/textures/wood.png")).ok();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(written.contains("mtllib tree_house.mtl\n") && written.contains("g front_side\n") && written.contains("usemtl oak_wood\n"), "{}", written);
+        assert!(library.contains("map_Kd ../../in/textures/wood.png\n"), "{}", library);
+        assert!(expected.is_some());
+        assert_eq!(texture, expected);
+        assert_eq!(parsed.materials[0].name, "oak_wood");
+    }
+
+    #[test]
+    fn paths_are_written_relative_to_the_library() {
+
+        assert_eq!(relative_to(Path::new("/models/textures/wood.png"), Path::new("/models")), PathBuf::from("textures/wood.png"));
+        assert_eq!(relative_to(Path::new("/models/../shared/wood.png"), Path::new("/export/today")), PathBuf::from("../../shared/wood.png"));
+        assert_eq!(relative_to(Path::new("wood.png"), Path::new("")), PathBuf::from("wood.png"));
+    }
+
+    #[test]
+    fn normals_can_be_left_out() {
+
+        let (original, _) = MeshData::from_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n".as_bytes(), "test.obj", &strict()).unwrap();
+        let options = ExportOptions { normals: false, groups: false, ..ExportOptions::default() };
+        let mut written = Vec::new();
+        original.write_obj(&mut written, &options, None).unwrap();
+
+        let written = String::from_utf8(written).unwrap();
+        assert!(!written.contains("vn") && !written.contains("g "));
+        let (parsed, _) = MeshData::from_obj(written.as_bytes(), "test.obj", &strict()).unwrap();
+        assert_same_geometry(&original, &parsed);
+    }
+}
@@ -0,0 +1,198 @@
+use std::{collections::HashMap, fmt, fs, hash::{DefaultHasher, Hash, Hasher}, io::{self, Read, Seek}, path::{Component, Path, PathBuf}};
+
+use super::error::ObjError;
+use super::ImporterRegistry;
+
+// where the files a model refers to are read from, such as its material libraries, textures
+// and glTF buffers. paths are those the model gives, joined to the directory of its filename
+pub trait FileSystem: fmt::Debug + Send + Sync {
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    // textures of local files are loaded when the model is uploaded, those of other file
+    // systems are read along with the model
+    fn is_local(&self) -> bool {
+        false
+    }
+    // tells file systems apart in the mesh cache, as a path may name other contents in each
+    fn id(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+// files on disk, what models are read from unless told otherwise
+#[derive(Debug, Default)]
+pub struct LocalFiles;
+
+impl FileSystem for LocalFiles {
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+// paths without `.` and `..`, with either slash, so the paths of a model find the entries of
+// its archive however they are written
+pub(super) fn normalize(path: &Path) -> PathBuf {
+
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut normal = PathBuf::from(if path.starts_with('/') { "/" } else { "" });
+    for component in path.split('/').map(|part| Path::new(part).components().next()) {
+        match component {
+            // a path can leave the directory it starts in
+            Some(Component::ParentDir) if !matches!(normal.components().next_back(), Some(Component::Normal(_))) => normal.push(".."),
+            Some(Component::ParentDir) => {
+                normal.pop();
+            },
+            Some(Component::CurDir) | None => (),
+            Some(component) => normal.push(component)
+        }
+    }
+    normal
+}
+
+// the files of a zip archive, read into memory when it is opened. entries are found under
+// the name of the archive, `bundle.zip/model.obj`, so messages name the archive they are in
+pub struct ZipFiles {
+    archive: PathBuf,
+    entries: HashMap<PathBuf, Vec<u8>>
+}
+
+impl ZipFiles {
+
+    pub fn from_reader<R: Read + Seek>(reader: R, filename: &str) -> Result<Self, ObjError> {
+
+        let invalid = |err: zip::result::ZipError| ObjError::invalid_data(filename, err.to_string());
+        let mut zip = zip::ZipArchive::new(reader).map_err(invalid)?;
+        let archive = normalize(Path::new(filename));
+        let mut entries = HashMap::new();
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i).map_err(invalid)?;
+            if file.is_dir() {
+                continue;
+            }
+            // entries escaping the archive with `..` can't be named by a model anyway
+            let Some(name) = file.enclosed_name() else {
+                continue;
+            };
+            let mut bytes = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut bytes).map_err(|source| ObjError::Io { file: format!("{}/{}", filename, name.display()), source })?;
+            entries.insert(archive.join(normalize(&name)), bytes);
+        }
+        Ok(Self { archive, entries })
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&[u8]> {
+        self.entries.get(&normalize(path)).map(Vec::as_slice)
+    }
+
+    // the entry shown when the archive is opened, with its contents: a file `importers` reads,
+    // the one nearest the top of the archive when there are several, then the first by name
+    pub fn find_model(&self, importers: &ImporterRegistry) -> Result<(&Path, &[u8]), ObjError> {
+
+        let mut models = self.entries.iter().filter(|(path, _)| importers.find_by_extension(&path.to_string_lossy()).is_some()).collect::<Vec<_>>();
+        models.sort_by_key(|(path, _)| (path.components().count(), path.as_path()));
+        match models.as_slice() {
+            [] => {
+                let mut names = self.entries.keys().map(|path| path.strip_prefix(&self.archive).unwrap_or(path).to_string_lossy()).collect::<Vec<_>>();
+                names.sort_unstable();
+                let message = format!("holds no model, only [{}]", names.join(", "));
+                Err(ObjError::invalid_data(&self.archive.to_string_lossy(), message))
+            },
+            [(model, bytes), rest @ ..] => {
+                if !rest.is_empty() {
+                    log::info!("{} holds {} models, showing {}", self.archive.display(), models.len(), model.display());
+                }
+                Ok((model, bytes))
+            }
+        }
+    }
+}
+
+impl FileSystem for ZipFiles {
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.get(path).map(<[u8]>::to_vec).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no such file in {}", self.archive.display())))
+    }
+
+    // the archive and a digest of its entries, so a changed archive isn't mistaken for the old one
+    fn id(&self) -> String {
+
+        let mut entries = self.entries.iter().collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|(path, _)| path.as_path());
+        let mut hasher = DefaultHasher::new();
+        entries.hash(&mut hasher);
+        format!("zip {} {:016x}", self.archive.display(), hasher.finish())
+    }
+}
+
+impl fmt::Debug for ZipFiles {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZipFiles").field("archive", &self.archive).field("entries", &self.entries.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{LoadOptions, ParseMode, SceneData, TextureMap};
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn strict() -> LoadOptions {
+        LoadOptions { mode: ParseMode::Strict, ..LoadOptions::default() }
+    }
+
+    #[test]
+    fn zip_bundles_show_their_model_with_its_library_and_textures() {
+
+        let filename = fixture("bundle.zip");
+        let (scene, report) = SceneData::from_file(&filename, &strict()).unwrap();
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        assert_eq!(report.libraries, vec![PathBuf::from(format!("{}/model/box.mtl", filename))]);
+
+        // the library names the texture with backslashes and `..`
+        let material = &scene.meshes[0].materials[0];
+        assert_eq!(material.name, "checker");
+        assert!(material.maps.is_empty());
+        assert_eq!(material.embedded.get(&TextureMap::Diffuse), Some(&fs::read(fixture("quad checker.png")).unwrap()));
+
+        // from memory, as bundles are downloaded
+        let bytes = fs::read(&filename).unwrap();
+        let (from_memory, _) = SceneData::from_reader(&bytes[..], "download.zip", &strict()).unwrap();
+        assert_eq!(from_memory, scene);
+    }
+
+    #[test]
+    fn archives_without_a_model_say_what_they_hold() {
+
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        zip.start_file("notes.txt", zip::write::SimpleFileOptions::default()).unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+        let err = SceneData::from_reader(&bytes[..], "notes.zip", &strict()).unwrap_err();
+        assert_eq!(err.to_string(), "notes.zip: holds no model, only [notes.txt]");
+    }
+
+    #[test]
+    fn gzip_models_are_read_as_the_file_inside() {
+
+        let (scene, _) = SceneData::from_file(&fixture("triangle.obj.gz"), &strict()).unwrap();
+        assert_eq!(scene.meshes[0].indices.len(), 3);
+    }
+
+    #[test]
+    fn paths_find_entries_however_they_are_written() {
+
+        assert_eq!(normalize(Path::new("bundle.zip/models/../textures/./wood.png")), PathBuf::from("bundle.zip/textures/wood.png"));
+        assert_eq!(normalize(Path::new("bundle.zip/textures\\wood.png")), PathBuf::from("bundle.zip/textures/wood.png"));
+        assert_eq!(normalize(Path::new("./bundle.zip//model.obj")), PathBuf::from("bundle.zip/model.obj"));
+        assert_eq!(normalize(Path::new("/assets/../bundle.zip")), PathBuf::from("/bundle.zip"));
+        assert_eq!(normalize(Path::new("../bundle.zip/model.obj")), PathBuf::from("../bundle.zip/model.obj"));
+    }
+}
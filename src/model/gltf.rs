@@ -0,0 +1,509 @@
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+use base64::Engine as _;
+use cgmath::{InnerSpace, Matrix3, Matrix4, One, Quaternion, SquareMatrix, Vector3};
+use ::gltf::mesh::Mode;
+
+use super::bounds::Aabb;
+use super::error::{LoadReport, ObjError};
+use super::files::FileSystem;
+use super::mtl::{MaterialData, TextureMap};
+use super::normals;
+use super::{LoadOptions, MaterialRange, MeshData, SceneData, Submesh};
+use crate::instance::Instance;
+
+// where the contents of a uri are: in the uri itself for data uris, otherwise in a file
+// relative to the model, its name percent-encoded
+enum Uri {
+    Data(Vec<u8>),
+    File(PathBuf)
+}
+
+fn resolve_uri(uri: &str, dir: &Path, filename: &str) -> Result<Uri, ObjError> {
+
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, encoded) = data.split_once(";base64,")
+            .ok_or_else(|| ObjError::invalid_data(filename, "only base64 data uris are supported".to_string()))?;
+        return base64::engine::general_purpose::STANDARD.decode(encoded)
+            .map(Uri::Data)
+            .map_err(|err| ObjError::invalid_data(filename, format!("bad data uri: {}", err)));
+    }
+    let name = urlencoding::decode(uri).map_err(|err| ObjError::invalid_data(filename, format!("bad uri `{}`: {}", uri, err)))?;
+    Ok(Uri::File(dir.join(&*name)))
+}
+
+// the contents of every buffer, reading external ones as libraries of the model so the
+// cache notices when they change
+fn read_buffers(document: &::gltf::Document, mut blob: Option<Vec<u8>>, dir: &Path, filename: &str, files: &dyn FileSystem, report: &mut LoadReport) -> Result<Vec<Vec<u8>>, ObjError> {
+
+    document.buffers().map(|buffer| {
+        let data = match buffer.source() {
+            ::gltf::buffer::Source::Bin => blob.take().ok_or_else(|| ObjError::invalid_data(filename, "the binary chunk is missing".to_string()))?,
+            ::gltf::buffer::Source::Uri(uri) => match resolve_uri(uri, dir, filename)? {
+                Uri::Data(data) => data,
+                Uri::File(path) => {
+                    let data = files.read(&path).map_err(|source| ObjError::Io { file: path.to_string_lossy().to_string(), source })?;
+                    report.libraries.push(path);
+                    data
+                }
+            }
+        };
+        if data.len() < buffer.length() {
+            return Err(ObjError::invalid_data(filename, format!("buffer {} holds {} bytes rather than {}", buffer.index(), data.len(), buffer.length())));
+        }
+        Ok(data)
+    }).collect()
+}
+
+// an image is either a file, loaded when the model is uploaded, or encoded in the model
+fn read_image(image: &::gltf::Image, buffers: &[Vec<u8>], dir: &Path, filename: &str) -> Result<Uri, ObjError> {
+
+    match image.source() {
+        ::gltf::image::Source::View { view, .. } => {
+            let start = view.offset();
+            let bytes = start.checked_add(view.length())
+                .and_then(|end| buffers.get(view.buffer().index())?.get(start..end))
+                .ok_or_else(|| ObjError::invalid_data(filename, format!("the view of image {} runs past the end of its buffer", image.index())))?;
+            Ok(Uri::Data(bytes.to_vec()))
+        }
+        ::gltf::image::Source::Uri { uri, .. } => resolve_uri(uri, dir, filename)
+    }
+}
+
+// approximates metallic-roughness with the colours and exponent of the Phong shading model.
+// metals reflect in their base colour. it stays their diffuse colour too, as without
+// reflections of their surroundings they would otherwise be black
+fn material(name: &str, base_color: [f32; 4], metallic: f32, roughness: f32, opaque: bool) -> MaterialData {
+
+    let [r, g, b, alpha] = base_color;
+    let specular = [r, g, b].map(|channel| 0.04 + (channel - 0.04) * metallic);
+    MaterialData {
+        ambient: [r, g, b],
+        diffuse: [r, g, b],
+        specular,
+        shininess: (2.0 / roughness.max(0.01).powi(4) - 2.0).clamp(1.0, 1024.0),
+        dissolve: if opaque { 1.0 } else { alpha },
+        ..MaterialData::new(name)
+    }
+}
+
+// what primitives without a material are drawn with, as the specification defines it
+fn default_material() -> MaterialData {
+    material("default", [1.0; 4], 1.0, 1.0, true)
+}
+
+fn read_material(gltf_material: &::gltf::Material, images: &[Option<Uri>]) -> MaterialData {
+
+    let pbr = gltf_material.pbr_metallic_roughness();
+    let name = gltf_material.name().map(str::to_string).unwrap_or_else(|| format!("material {}", gltf_material.index().unwrap_or(0)));
+    let opaque = gltf_material.alpha_mode() == ::gltf::material::AlphaMode::Opaque;
+    let mut data = material(&name, pbr.base_color_factor(), pbr.metallic_factor(), pbr.roughness_factor(), opaque);
+
+    let textures = [
+        (TextureMap::Diffuse, pbr.base_color_texture().map(|info| info.texture())),
+        (TextureMap::Normal, gltf_material.normal_texture().map(|info| info.texture()))
+    ];
+    for (map, texture) in textures {
+        match texture.and_then(|texture| images[texture.source().index()].as_ref()) {
+            Some(Uri::File(path)) => {
+                data.maps.insert(map, path.clone());
+            }
+            Some(Uri::Data(bytes)) => {
+                data.embedded.insert(map, bytes.clone());
+            }
+            None => ()
+        }
+    }
+    data
+}
+
+// the indices of the triangles, line segments or points a primitive draws, in list form
+enum Elements {
+    Triangles(Vec<[u32; 3]>),
+    Lines(Vec<u32>),
+    Points(Vec<u32>)
+}
+
+fn assemble(mode: Mode, indices: &[u32]) -> Elements {
+
+    let n = indices.len();
+    match mode {
+        Mode::Triangles => Elements::Triangles(indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect()),
+        // every other triangle of a strip is turned around, so they all wind the same way
+        Mode::TriangleStrip => Elements::Triangles((0..n.saturating_sub(2)).map(|i| {
+            [indices[i], indices[i + 1 + i % 2], indices[i + 2 - i % 2]]
+        }).collect()),
+        Mode::TriangleFan => Elements::Triangles((0..n.saturating_sub(2)).map(|i| {
+            [indices[i + 1], indices[i + 2], indices[0]]
+        }).collect()),
+        Mode::Lines => Elements::Lines(indices[..n - n % 2].to_vec()),
+        Mode::LineStrip | Mode::LineLoop => {
+            let mut lines = indices.windows(2).flatten().copied().collect::<Vec<_>>();
+            if mode == Mode::LineLoop && n > 2 {
+                lines.extend([indices[n - 1], indices[0]]);
+            }
+            Elements::Lines(lines)
+        }
+        Mode::Points => Elements::Points(indices.to_vec())
+    }
+}
+
+impl Elements {
+
+    // drops the triangles, segments and points using a vertex past `count` whole, so the
+    // others keep their corners
+    fn within(self, count: usize) -> Self {
+
+        let valid = |vertex: &u32| (*vertex as usize) < count;
+        match self {
+            Elements::Triangles(triangles) => Elements::Triangles(triangles.into_iter().filter(|triangle| triangle.iter().all(valid)).collect()),
+            Elements::Lines(lines) => Elements::Lines(lines.chunks_exact(2).filter(|segment| segment.iter().all(valid)).flatten().copied().collect()),
+            Elements::Points(points) => Elements::Points(points.into_iter().filter(valid).collect())
+        }
+    }
+}
+
+// the vertex attributes of one primitive, those the file leaves out already filled in
+struct Vertices {
+    positions: Vec<[f32; 3]>,
+    normals: Option<Vec<[f32; 3]>>,
+    texcoords: Vec<[f32; 2]>,
+    colors: Vec<[f32; 3]>
+}
+
+impl Vertices {
+
+    fn push_to(&self, mesh: &mut MeshData, vertex: usize, normal: [f32; 3]) {
+
+        mesh.positions.push(self.positions[vertex]);
+        mesh.normals.push(normal);
+        if let Some(texcoord) = self.texcoords.get(vertex) {
+            mesh.texcoords.push(*texcoord);
+        }
+        if let Some(color) = self.colors.get(vertex) {
+            mesh.colors.push(*color);
+        }
+    }
+}
+
+// what every mesh of a file reads from
+struct Resources<'a> {
+    filename: &'a str,
+    buffers: Vec<Vec<u8>>,
+    materials: Vec<MaterialData>,
+    // when any primitive has vertex colours, every mesh gets them
+    colors: bool
+}
+
+// a glTF mesh becomes one mesh with a submesh for each primitive drawing triangles
+fn read_mesh(gltf_mesh: &::gltf::Mesh, resources: &Resources, options: &LoadOptions, report: &mut LoadReport) -> Result<MeshData, ObjError> {
+
+    let mut mesh = MeshData::default();
+    let name = gltf_mesh.name().map(str::to_string).unwrap_or_else(|| format!("mesh {}", gltf_mesh.index()));
+    let primitive_count = gltf_mesh.primitives().len();
+    let texcoords = gltf_mesh.primitives().any(|primitive| primitive.get(&::gltf::Semantic::TexCoords(0)).is_some());
+    // each material is copied into the mesh the first time one of its primitives uses it
+    let mut material_slots: HashMap<Option<usize>, usize> = HashMap::new();
+
+    for primitive in gltf_mesh.primitives() {
+        let reader = primitive.reader(|buffer| resources.buffers.get(buffer.index()).map(Vec::as_slice));
+        let Some(positions) = reader.read_positions() else {
+            options.mode.problem(ObjError::invalid_data(resources.filename, format!("a primitive of `{}` has no positions", name)), report)?;
+            continue;
+        };
+        let positions = positions.collect::<Vec<_>>();
+        let count = positions.len();
+
+        let material_index = primitive.material().index();
+        let material = *material_slots.entry(material_index).or_insert_with(|| {
+            mesh.materials.push(material_index.map_or_else(default_material, |i| resources.materials[i].clone()));
+            mesh.materials.len() - 1
+        });
+        // vertex colours multiply the base colour, which they then stand in for
+        let [r, g, b, _] = primitive.material().pbr_metallic_roughness().base_color_factor();
+        let colors = match reader.read_colors(0) {
+            Some(colors) => colors.into_rgba_f32().map(|[cr, cg, cb, _]| [cr * r, cg * g, cb * b]).collect(),
+            None if resources.colors => vec![[r, g, b]; count],
+            None => Vec::new()
+        };
+        // glTF puts v = 0 at the top of the image, meshes keep it at the bottom as OBJ does
+        let texcoords = match reader.read_tex_coords(0) {
+            Some(texcoords) => texcoords.into_f32().map(|[u, v]| [u, 1.0 - v]).collect(),
+            None if texcoords => vec![[0.0; 2]; count],
+            None => Vec::new()
+        };
+        let normals = reader.read_normals().map(|normals| normals.collect::<Vec<_>>());
+        // attributes have one value per position in a valid file
+        let matching = normals.as_ref().is_none_or(|normals| normals.len() == count)
+            && (texcoords.is_empty() || texcoords.len() == count)
+            && (colors.is_empty() || colors.len() == count);
+        if !matching {
+            return Err(ObjError::invalid_data(resources.filename, format!("the attributes of a primitive of `{}` have different lengths", name)));
+        }
+        let vertices = Vertices { positions, normals, texcoords, colors };
+
+        let indices = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+            None => (0..count as u32).collect()
+        };
+        if let Some(&index) = indices.iter().find(|&&index| index as usize >= count) {
+            options.mode.problem(ObjError::invalid_data(resources.filename, format!("index {} of a primitive of `{}` is out of range, it has {} vertices", index, name, count)), report)?;
+        }
+
+        let base = mesh.positions.len() as u32;
+        match assemble(primitive.mode(), &indices).within(count) {
+            Elements::Triangles(triangles) => {
+                let start = mesh.indices.len() as u32;
+                match &vertices.normals {
+                    Some(normals) => {
+                        for (vertex, &normal) in normals.iter().enumerate() {
+                            vertices.push_to(&mut mesh, vertex, normal);
+                        }
+                        mesh.indices.extend(triangles.iter().flatten().map(|&vertex| base + vertex));
+                    }
+                    // without normals a primitive is shaded flat, each corner getting its own vertex
+                    None => {
+                        let groups = vec![normals::FLAT; triangles.len()];
+                        let generated = normals::generate(&vertices.positions, &triangles, &groups, options.crease_angle.into());
+                        for (corner, &vertex) in triangles.iter().flatten().enumerate() {
+                            vertices.push_to(&mut mesh, vertex as usize, generated[corner]);
+                            mesh.indices.push(base + corner as u32);
+                        }
+                    }
+                }
+                let indices = start..mesh.indices.len() as u32;
+                let bounds = Aabb::from_points(mesh.indices[start as usize..].iter().map(|&i| &mesh.positions[i as usize]));
+                mesh.submeshes.push(Submesh {
+                    name: if primitive_count > 1 { format!("{} {}", name, primitive.index()) } else { name.clone() },
+                    indices: indices.clone(),
+                    material_ranges: vec![MaterialRange { material, indices }],
+                    bounds
+                });
+            }
+            Elements::Lines(lines) => {
+                for vertex in 0..count {
+                    vertices.push_to(&mut mesh, vertex, vertices.normals.as_ref().map_or([0.0; 3], |normals| normals[vertex]));
+                }
+                mesh.line_indices.extend(lines.iter().map(|&vertex| base + vertex));
+            }
+            Elements::Points(points) => {
+                for vertex in 0..count {
+                    vertices.push_to(&mut mesh, vertex, vertices.normals.as_ref().map_or([0.0; 3], |normals| normals[vertex]));
+                }
+                mesh.point_indices.extend(points.iter().map(|&vertex| base + vertex));
+            }
+        }
+    }
+    Ok(mesh)
+}
+
+// a node's world matrix as an instance. nodes only hold translation, rotation and scale, but
+// parents scaled unevenly with rotated children can add shear, which an instance can't hold
+fn to_instance(matrix: Matrix4<f32>) -> Instance {
+
+    let linear = Matrix3::from_cols(matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate());
+    let mut scaling = Vector3::new(linear.x.magnitude(), linear.y.magnitude(), linear.z.magnitude());
+    // a mirroring matrix is a rotation with one axis scaled negatively
+    if linear.determinant() < 0.0 {
+        scaling.x = -scaling.x;
+    }
+    let rotation = if scaling.x * scaling.y * scaling.z != 0.0 {
+        Quaternion::from(Matrix3::from_cols(linear.x / scaling.x, linear.y / scaling.y, linear.z / scaling.z)).normalize()
+    } else {
+        Quaternion::one()
+    };
+    Instance { position: matrix.w.truncate(), rotation, scaling }
+}
+
+// adds an instance of its mesh for the node and each of its descendants. a valid file has no
+// cycles, `depth` stops a broken one from recursing forever
+fn place(node: ::gltf::Node, parent: Matrix4<f32>, depth: usize, instances: &mut [Vec<Instance>]) {
+
+    let matrix = parent * Matrix4::from(node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        instances[mesh.index()].push(to_instance(matrix));
+    }
+    if depth > 0 {
+        for child in node.children() {
+            place(child, matrix, depth - 1, instances);
+        }
+    }
+}
+
+// reads a .gltf or .glb file with its buffers into a mesh for each glTF mesh, placed by the
+// nodes of the default scene. tangents are left to be generated as for other formats
+pub fn parse(bytes: &[u8], filename: &str, options: &LoadOptions) -> Result<(SceneData, LoadReport), ObjError> {
+
+    let ::gltf::Gltf { document, blob } = ::gltf::Gltf::from_slice(bytes).map_err(|err| ObjError::invalid_data(filename, err.to_string()))?;
+    let dir = Path::new(filename).parent().unwrap_or_else(|| Path::new(""));
+    let mut report = LoadReport::default();
+    let buffers = read_buffers(&document, blob, dir, filename, options.files.as_ref(), &mut report)?;
+
+    let mut images = Vec::new();
+    for image in document.images() {
+        match read_image(&image, &buffers, dir, filename) {
+            Ok(uri) => images.push(Some(uri)),
+            Err(err) => {
+                options.mode.problem(err, &mut report)?;
+                images.push(None);
+            }
+        }
+    }
+    let resources = Resources {
+        filename,
+        materials: document.materials().map(|material| read_material(&material, &images)).collect(),
+        colors: document.meshes().flat_map(|mesh| mesh.primitives()).any(|primitive| primitive.get(&::gltf::Semantic::Colors(0)).is_some()),
+        buffers
+    };
+
+    let meshes = document.meshes()
+        .map(|gltf_mesh| read_mesh(&gltf_mesh, &resources, options, &mut report))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // a file without scenes has nothing to show, its meshes are then each shown once where they are
+    let mut instances = vec![Vec::new(); meshes.len()];
+    match document.default_scene().or_else(|| document.scenes().next()) {
+        Some(scene) => {
+            for node in scene.nodes() {
+                place(node, Matrix4::one(), document.nodes().len(), &mut instances);
+            }
+        }
+        None => {
+            for mesh_instances in &mut instances {
+                mesh_instances.push(Instance::identity());
+            }
+        }
+    }
+    Ok((SceneData { meshes, instances: Some(instances) }, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ParseMode;
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn parse_fixture(name: &str) -> (SceneData, LoadReport) {
+        let filename = fixture(name);
+        let options = LoadOptions { mode: ParseMode::Strict, ..LoadOptions::default() };
+        SceneData::from_gltf_bytes(&std::fs::read(&filename).unwrap(), &filename, &options).unwrap()
+    }
+
+    // a file with one buffer holding `bytes`, viewed as a whole by every accessor and image
+    fn embedded(bytes: &[u8], accessors: &str, primitive: &str, images: &str) -> Vec<u8> {
+        format!(
+            r#"{{"asset":{{"version":"2.0"}},"meshes":[{{"primitives":[{}]}}],"accessors":[{}],"images":[{}],
+                "bufferViews":[{{"buffer":0,"byteLength":{}}}],"buffers":[{{"byteLength":{},"uri":"data:application/octet-stream;base64,{}"}}]}}"#,
+            primitive, accessors, images, bytes.len(), bytes.len(), base64::engine::general_purpose::STANDARD.encode(bytes)
+        ).into_bytes()
+    }
+
+    #[test]
+    fn out_of_range_indices_drop_only_their_triangles() {
+
+        let positions: [[f32; 3]; 4] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+        let indices: [u16; 10] = [0, 1, 2, 0, 7, 3, 0, 2, 3, 0];
+        let mut bytes = bytemuck::cast_slice::<_, u8>(&positions).to_vec();
+        bytes.extend_from_slice(bytemuck::cast_slice(&indices));
+        let accessors = r#"{"bufferView":0,"componentType":5126,"count":4,"type":"VEC3","min":[0,0,0],"max":[1,1,0]},
+            {"bufferView":0,"byteOffset":48,"componentType":5123,"count":9,"type":"SCALAR"}"#;
+        let gltf = embedded(&bytes, accessors, r#"{"attributes":{"POSITION":0},"indices":1}"#, "");
+
+        let strict = LoadOptions { mode: ParseMode::Strict, ..LoadOptions::default() };
+        assert!(parse(&gltf, "bad index.gltf", &strict).is_err());
+        let (scene, report) = parse(&gltf, "bad index.gltf", &LoadOptions::default()).unwrap();
+        assert_eq!(report.warnings.len(), 1);
+        // the triangles before and after the broken one are read as they are
+        let mesh = &scene.meshes[0];
+        let corners = mesh.indices.iter().map(|&i| mesh.positions[i as usize]).collect::<Vec<_>>();
+        assert_eq!(corners, [0, 1, 2, 0, 2, 3].map(|i| positions[i]));
+    }
+
+    #[test]
+    fn image_views_past_their_buffer_are_reported() {
+
+        let gltf = embedded(&[0; 8], "", "", r#"{"bufferView":0,"mimeType":"image/png"}"#);
+        let gltf = String::from_utf8(gltf).unwrap().replace(r#""byteLength":8}],"buffers""#, r#""byteOffset":4,"byteLength":8}],"buffers""#);
+
+        let strict = LoadOptions { mode: ParseMode::Strict, ..LoadOptions::default() };
+        assert!(parse(gltf.as_bytes(), "image.gltf", &strict).is_err());
+        let (_, report) = parse(gltf.as_bytes(), "image.gltf", &LoadOptions::default()).unwrap();
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].to_string().contains("runs past"), "{}", report.warnings[0]);
+    }
+
+    #[test]
+    fn data_uri_triangle_is_shaded_flat_and_placed_by_its_node() {
+
+        let (scene, report) = parse_fixture("triangle.gltf");
+        assert!(report.libraries.is_empty());
+        let mesh = &scene.meshes[0];
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+        assert!(mesh.normals.iter().all(|&normal| normal == [0.0, 0.0, 1.0]));
+        assert_eq!(mesh.materials[0].diffuse, [1.0, 0.0, 0.0]);
+        assert_eq!(scene.instances.as_ref().unwrap()[0][0].position, Vector3::new(0.0, 0.0, 5.0));
+
+        let flat = scene.flatten();
+        assert_eq!(flat.positions[1], [1.0, 0.0, 5.0]);
+        assert_eq!(flat.submeshes[0].bounds, Aabb { min: [0.0, 0.0, 5.0], max: [1.0, 1.0, 5.0] });
+    }
+
+    #[test]
+    fn glb_nodes_become_instances_of_a_textured_mesh() {
+
+        let (scene, _) = parse_fixture("cubes.glb");
+        let mesh = &scene.meshes[0];
+        assert_eq!((mesh.positions.len(), mesh.indices.len()), (24, 36));
+        assert!(mesh.has_tangents());
+        let image = &mesh.materials[0].embedded[&TextureMap::Diffuse];
+        assert!(image.starts_with(b"\x89PNG"));
+
+        // the child inherits the scale of its parent, which applies to its translation too
+        let instances = &scene.instances.as_ref().unwrap()[0];
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances[0].position, Vector3::new(2.0, 0.0, 0.0));
+        assert_eq!(instances[0].scaling, Vector3::new(2.0, 2.0, 2.0));
+        let turned = instances[1].to_matrix() * Vector3::unit_x().extend(0.0);
+        assert!((turned.truncate() - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-5, "{:?}", turned);
+        assert_eq!(scene.flatten().submeshes.len(), 2);
+    }
+
+    #[test]
+    fn mirrored_nodes_are_turned_around_when_flattened() {
+
+        let (scene, _) = parse_fixture("mirrored.gltf");
+        assert!(scene.has_mirrored_instances());
+        assert!(!parse_fixture("triangle.gltf").0.has_mirrored_instances());
+
+        // both copies face +z, the mirrored one by being wound the other way round
+        let flat = scene.flatten();
+        assert_eq!(flat.positions[4], [-3.0, 0.0, 0.0]);
+        for triangle in flat.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| Vector3::from(flat.positions[triangle[i] as usize]));
+            assert!((b - a).cross(c - a).z > 0.0, "{:?}", triangle);
+        }
+        assert!(flat.normals.iter().all(|&normal| normal == [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn external_buffer_and_image_files_are_read_next_to_the_model() {
+
+        let (scene, report) = parse_fixture("quads.gltf");
+        assert_eq!(report.libraries, vec![PathBuf::from(fixture("quads.bin"))]);
+        let mesh = &scene.meshes[0];
+        assert_eq!(mesh.submeshes.len(), 2);
+        assert_eq!(mesh.materials[1].maps[&TextureMap::Diffuse], PathBuf::from(fixture("quad checker.png")));
+        // the strip draws the second quad with two triangles wound the same way
+        assert_eq!(mesh.submeshes[1].indices, 6..12);
+        let corners = mesh.indices[6..12].iter().map(|&i| mesh.positions[i as usize]).collect::<Vec<_>>();
+        assert_eq!(&corners[..3], &[[2.0, 0.0, 0.0], [3.0, 0.0, 0.0], [2.0, 1.0, 0.0]]);
+        assert_eq!(&corners[3..], &[[3.0, 0.0, 0.0], [3.0, 1.0, 0.0], [2.0, 1.0, 0.0]]);
+        // vertex colours are multiplied by the base colour, the quad without them takes it as is
+        let color_at = |position: [f32; 3]| mesh.colors[mesh.positions.iter().position(|&p| p == position).unwrap()];
+        assert_eq!(color_at([1.0, 0.0, 0.0]), [0.0, 0.5, 0.0]);
+        assert_eq!(color_at([3.0, 1.0, 0.0]), [1.0, 1.0, 0.0]);
+    }
+}
@@ -0,0 +1,297 @@
+use std::{fmt, path::Path, sync::Arc};
+
+use super::error::{LoadReport, ObjError};
+use super::{gltf, obj, ply, stl};
+use super::{LoadOptions, SceneData};
+
+// reads one file format into meshes with their materials. importers return what they read,
+// welding and tangent generation are left to the registry so every format gets them.
+// a format without a scene graph returns a single mesh, `SceneData::from` makes one of it.
+// every attribute of a mesh has a value for each position or is left empty, normals that
+// are left out are generated
+pub trait ModelImporter: Send + Sync {
+
+    // shown in messages, such as "PLY"
+    fn name(&self) -> &str;
+    // lowercase and without the dot
+    fn extensions(&self) -> &[&str];
+    // whether the contents of a file are in this format, for files with an extension no
+    // importer claims. formats without a signature can leave it false
+    fn sniff(&self, _bytes: &[u8]) -> bool {
+        false
+    }
+    // `progress` may be called with the fraction of the file read so far
+    fn import(&self, bytes: &[u8], filename: &str, options: &LoadOptions, progress: &(dyn Fn(f32) + Sync)) -> Result<(SceneData, LoadReport), ObjError>;
+}
+
+struct ObjImporter;
+
+impl ModelImporter for ObjImporter {
+
+    fn name(&self) -> &str {
+        "OBJ"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["obj"]
+    }
+
+    // text whose first statement is an OBJ one
+    fn sniff(&self, bytes: &[u8]) -> bool {
+
+        const KEYWORDS: &[&str] = &["v", "vn", "vt", "vp", "f", "l", "p", "o", "g", "s", "mtllib", "usemtl"];
+        let start = &bytes[..bytes.len().min(4096)];
+        let text = match std::str::from_utf8(start) {
+            Ok(text) => text,
+            // the start may end inside a character
+            Err(err) => std::str::from_utf8(&start[..err.valid_up_to()]).unwrap_or("")
+        };
+        text.lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .and_then(|line| line.split_whitespace().next())
+            .is_some_and(|keyword| KEYWORDS.contains(&keyword))
+    }
+
+    fn import(&self, bytes: &[u8], filename: &str, options: &LoadOptions, progress: &(dyn Fn(f32) + Sync)) -> Result<(SceneData, LoadReport), ObjError> {
+        obj::parse(bytes, filename, options, progress).map(|(mesh, report)| (mesh.into(), report))
+    }
+}
+
+struct StlImporter;
+
+impl ModelImporter for StlImporter {
+
+    fn name(&self) -> &str {
+        "STL"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["stl"]
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        stl::sniff(bytes)
+    }
+
+    fn import(&self, bytes: &[u8], filename: &str, options: &LoadOptions, _progress: &(dyn Fn(f32) + Sync)) -> Result<(SceneData, LoadReport), ObjError> {
+        stl::parse(bytes, filename, options).map(|(mesh, report)| (mesh.into(), report))
+    }
+}
+
+struct PlyImporter;
+
+impl ModelImporter for PlyImporter {
+
+    fn name(&self) -> &str {
+        "PLY"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ply"]
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        bytes.starts_with(b"ply\n") || bytes.starts_with(b"ply\r\n")
+    }
+
+    fn import(&self, bytes: &[u8], filename: &str, options: &LoadOptions, _progress: &(dyn Fn(f32) + Sync)) -> Result<(SceneData, LoadReport), ObjError> {
+        ply::parse(bytes, filename, options).map(|(mesh, report)| (mesh.into(), report))
+    }
+}
+
+struct GltfImporter;
+
+impl ModelImporter for GltfImporter {
+
+    fn name(&self) -> &str {
+        "glTF"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["gltf", "glb"]
+    }
+
+    // the binary container has a magic number, the JSON form has to start with its asset
+    fn sniff(&self, bytes: &[u8]) -> bool {
+
+        let start = &bytes[..bytes.len().min(256)];
+        let json = start.iter().position(|byte| !byte.is_ascii_whitespace()).is_some_and(|first| start[first] == b'{');
+        bytes.starts_with(b"glTF") || json && start.windows(7).any(|window| window == b"\"asset\"")
+    }
+
+    fn import(&self, bytes: &[u8], filename: &str, options: &LoadOptions, _progress: &(dyn Fn(f32) + Sync)) -> Result<(SceneData, LoadReport), ObjError> {
+        gltf::parse(bytes, filename, options)
+    }
+}
+
+// the importers a file can be read with. one is picked by the extension of the file, and when
+// no importer claims it, by the contents. the most recently registered importer comes first
+// both ways, so downstream importers can take over formats read here too
+#[derive(Clone)]
+pub struct ImporterRegistry {
+    importers: Vec<Arc<dyn ModelImporter>>
+}
+
+impl ImporterRegistry {
+
+    // a registry without any importers, `default` has those of every format read here
+    pub fn empty() -> Self {
+        Self { importers: Vec::new() }
+    }
+
+    pub fn register<I: ModelImporter + 'static>(&mut self, importer: I) {
+        self.importers.push(Arc::new(importer));
+    }
+
+    pub fn get_importers(&self) -> impl Iterator<Item = &dyn ModelImporter> {
+        self.importers.iter().rev().map(|importer| importer.as_ref())
+    }
+
+    pub fn find_by_extension(&self, filename: &str) -> Option<&dyn ModelImporter> {
+
+        let extension = Path::new(filename).extension()?.to_string_lossy().to_lowercase();
+        self.get_importers().find(|importer| importer.extensions().contains(&extension.as_str()))
+    }
+
+    pub fn find(&self, filename: &str, bytes: &[u8]) -> Option<&dyn ModelImporter> {
+        self.find_by_extension(filename).or_else(|| self.get_importers().find(|importer| importer.sniff(bytes)))
+    }
+
+    // reads a file with the importer `find` picks, and does what every format goes through after
+    pub fn import(&self, bytes: &[u8], filename: &str, options: &LoadOptions, progress: &(dyn Fn(f32) + Sync)) -> Result<(SceneData, LoadReport), ObjError> {
+
+        let importer = self.find(filename, bytes).ok_or_else(|| {
+            let extensions = self.get_importers().flat_map(|importer| importer.extensions().iter().copied()).collect::<Vec<_>>();
+            let message = format!("not a format any importer recognises, they read files ending in [{}]", extensions.join(", "));
+            ObjError::invalid_data(filename, message)
+        })?;
+        let (scene, report) = importer.import(bytes, filename, options, progress)?;
+        scene.finish(report, filename, options)
+    }
+}
+
+impl Default for ImporterRegistry {
+
+    fn default() -> Self {
+
+        let mut registry = Self::empty();
+        registry.register(ObjImporter);
+        registry.register(StlImporter);
+        registry.register(PlyImporter);
+        registry.register(GltfImporter);
+        registry
+    }
+}
+
+impl fmt::Debug for ImporterRegistry {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.get_importers().map(|importer| importer.name())).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{MeshData, ParseMode};
+
+    // a triangle per line of three corners, in files starting with TRI. normals are left to
+    // the registry
+    struct TriImporter;
+
+    impl ModelImporter for TriImporter {
+
+        fn name(&self) -> &str {
+            "TRI"
+        }
+
+        fn extensions(&self) -> &[&str] {
+            &["tri", "obj"]
+        }
+
+        fn sniff(&self, bytes: &[u8]) -> bool {
+            bytes.starts_with(b"TRI\n")
+        }
+
+        fn import(&self, bytes: &[u8], _filename: &str, _options: &LoadOptions, _progress: &(dyn Fn(f32) + Sync)) -> Result<(SceneData, LoadReport), ObjError> {
+
+            let mut mesh = MeshData::default();
+            for line in std::str::from_utf8(bytes).unwrap().lines().skip(1) {
+                let values = line.split_whitespace().map(|value| value.parse().unwrap()).collect::<Vec<f32>>();
+                for corner in values.chunks_exact(3) {
+                    mesh.indices.push(mesh.positions.len() as u32);
+                    mesh.positions.push([corner[0], corner[1], corner[2]]);
+                }
+            }
+            Ok((mesh.into(), LoadReport::default()))
+        }
+    }
+
+    fn found(registry: &ImporterRegistry, filename: &str, bytes: &[u8]) -> Option<String> {
+        registry.find(filename, bytes).map(|importer| importer.name().to_string())
+    }
+
+    #[test]
+    fn formats_are_told_apart_by_extension_then_contents() {
+
+        let registry = ImporterRegistry::default();
+        assert_eq!(found(&registry, "model.PLY", b"").as_deref(), Some("PLY"));
+        assert_eq!(found(&registry, "model.glb", b"").as_deref(), Some("glTF"));
+
+        let glb = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/cubes.glb")).unwrap();
+        let mut binary_stl = [0u8; 84 + 50];
+        binary_stl[80] = 1;
+        let sniffed = [
+            (&b"ply\nformat ascii 1.0\nend_header\n"[..], "PLY"),
+            (&glb[..], "glTF"),
+            (b"  {\n  \"asset\": {\"version\": \"2.0\"}\n}", "glTF"),
+            (&binary_stl[..], "STL"),
+            (b"solid cube\nendsolid cube\n", "STL"),
+            (b"# exported\n\nmtllib box.mtl\nv 0 0 0\n", "OBJ")
+        ];
+        for (bytes, name) in sniffed {
+            assert_eq!(found(&registry, "download.bin", bytes).as_deref(), Some(name));
+        }
+        assert_eq!(found(&registry, "notes.txt", b"shopping list\n"), None);
+        let err = registry.import(b"shopping list\n", "notes.txt", &LoadOptions::default(), &|_| ()).unwrap_err();
+        assert!(err.to_string().contains("[gltf, glb, ply, stl, obj]"), "{}", err);
+    }
+
+    #[test]
+    fn registered_importers_come_first_and_are_finished_like_the_others() {
+
+        let mut registry = ImporterRegistry::default();
+        registry.register(TriImporter);
+        assert_eq!(found(&registry, "scan.tri", b"").as_deref(), Some("TRI"));
+        assert_eq!(found(&registry, "scan.obj", b"v 0 0 0\n").as_deref(), Some("TRI"));
+        assert_eq!(found(&registry, "scan", b"TRI\n").as_deref(), Some("TRI"));
+
+        // the two triangles share an edge, whose corners are welded once they have normals
+        let options = LoadOptions { mode: ParseMode::Strict, importers: registry.clone(), ..LoadOptions::default() };
+        let source = b"TRI\n0 0 0 1 0 0 0 1 0\n1 0 0 1 1 0 0 1 0\n";
+        let (mesh, report) = MeshData::from_bytes(source, "scan.tri", &options, &|_| ()).unwrap();
+        assert_eq!((mesh.positions.len(), mesh.indices.len()), (4, 6));
+        assert_eq!(mesh.normals, vec![[0.0, 0.0, 1.0]; 4]);
+        assert_eq!(report.weld.merged, 2);
+        assert_eq!(mesh.vertices().len(), 4);
+    }
+
+    #[test]
+    fn meshes_with_attributes_of_other_lengths_are_rejected() {
+
+        let options = LoadOptions::default();
+        let triangle = || MeshData {
+            positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            indices: vec![0, 1, 2],
+            ..MeshData::default()
+        };
+        assert!(triangle().finish(LoadReport::default(), "triangle", &options).is_ok());
+
+        let short_texcoords = MeshData { texcoords: vec![[0.0; 2]; 2], ..triangle() };
+        let err = short_texcoords.finish(LoadReport::default(), "triangle", &options).unwrap_err();
+        assert!(err.to_string().contains("2 values of texture coordinates for 3 positions"), "{}", err);
+        let out_of_range = MeshData { line_indices: vec![0, 3], ..triangle() };
+        assert!(out_of_range.finish(LoadReport::default(), "triangle", &options).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use std::{collections::HashMap, io::BufRead, path::{Path, PathBuf}};
+
+use super::error::{LoadReport, ObjError, ParseMode};
+use super::tokenizer::{parse_floats, Line, LineReader};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureMap {
+    Ambient,
+    Diffuse,
+    Specular,
+    Shininess,
+    Dissolve,
+    Bump,
+    Normal,
+    Displacement
+}
+
+impl TextureMap {
+
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "map_Ka" => Some(TextureMap::Ambient),
+            "map_Kd" => Some(TextureMap::Diffuse),
+            "map_Ks" => Some(TextureMap::Specular),
+            "map_Ns" => Some(TextureMap::Shininess),
+            "map_d" => Some(TextureMap::Dissolve),
+            "map_Bump" | "map_bump" | "bump" => Some(TextureMap::Bump),
+            "norm" => Some(TextureMap::Normal),
+            "disp" => Some(TextureMap::Displacement),
+            _ => None
+        }
+    }
+
+    // the statement written for each map when exporting
+    pub fn keyword(self) -> &'static str {
+        match self {
+            TextureMap::Ambient => "map_Ka",
+            TextureMap::Diffuse => "map_Kd",
+            TextureMap::Specular => "map_Ks",
+            TextureMap::Shininess => "map_Ns",
+            TextureMap::Dissolve => "map_d",
+            TextureMap::Bump => "map_Bump",
+            TextureMap::Normal => "norm",
+            TextureMap::Displacement => "disp"
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialData {
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub shininess: f32,
+    pub dissolve: f32,
+    pub illumination: u32,
+    // image files, already resolved relative to the library that referenced them
+    pub maps: HashMap<TextureMap, PathBuf>,
+    // encoded images stored in the model file itself, for maps without a file of their own
+    pub embedded: HashMap<TextureMap, Vec<u8>>
+}
+
+impl MaterialData {
+
+    // starting values for a `newmtl` block, with the ambient colour MTL files assume
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ambient: [0.2; 3],
+            diffuse: [0.8; 3],
+            specular: [0.0; 3],
+            shininess: 32.0,
+            dissolve: 1.0,
+            illumination: 2,
+            maps: HashMap::new(),
+            embedded: HashMap::new()
+        }
+    }
+}
+
+impl Default for MaterialData {
+
+    // what faces without a `usemtl` are drawn with
+    fn default() -> Self {
+        let color = [0.3, 0.2, 0.5];
+        Self {
+            ambient: color,
+            diffuse: color,
+            specular: color,
+            ..Self::new("default")
+        }
+    }
+}
+
+// number of arguments taken by each texture map option, `-o`, `-s` and `-t` take up to 3
+const MAP_OPTIONS: &[(&str, usize)] = &[
+    ("-blendu", 1), ("-blendv", 1), ("-bm", 1), ("-boost", 1), ("-cc", 1), ("-clamp", 1),
+    ("-imfchan", 1), ("-mm", 2), ("-o", 3), ("-s", 3), ("-t", 3), ("-texres", 1), ("-type", 1)
+];
+
+// strips the options in front of the file name of a texture map statement
+fn map_file_name(rest: &str) -> &str {
+
+    let mut rest = rest.trim_start();
+    while rest.starts_with('-') {
+        let option = rest.split_whitespace().next().unwrap_or(rest);
+        let Some(&(_, max_args)) = MAP_OPTIONS.iter().find(|(name, _)| *name == option) else {
+            break;
+        };
+        rest = rest[option.len()..].trim_start();
+        for arg in 0..max_args {
+            let token = rest.split_whitespace().next().unwrap_or(rest);
+            // the optional arguments of -o, -s and -t are numbers, so the file name ends them
+            if arg > 0 && token.parse::<f32>().is_err() {
+                break;
+            }
+            rest = rest[token.len()..].trim_start();
+        }
+    }
+    rest
+}
+
+fn parse_color(line: &Line, rest: &str) -> Result<[f32; 3], ObjError> {
+
+    // a single value is a shade of grey
+    let parsed = parse_floats::<3>(line, rest, 1, "1 or 3")?;
+    if rest.split_whitespace().count() == 1 {
+        Ok([parsed[0]; 3])
+    } else {
+        Ok(parsed)
+    }
+}
+
+fn parse_line(line: &Line, dir: &Path, materials: &mut Vec<MaterialData>) -> Result<(), ObjError> {
+
+    let Some((keyword, rest)) = line.split_keyword() else {
+        return Ok(());
+    };
+
+    if keyword == "newmtl" {
+        materials.push(MaterialData::new(rest));
+        return Ok(());
+    }
+    // statements before the first `newmtl` have nothing to apply to
+    let Some(material) = materials.last_mut() else {
+        return Ok(());
+    };
+    match keyword {
+        "Ka" => material.ambient = parse_color(line, rest)?,
+        "Kd" => material.diffuse = parse_color(line, rest)?,
+        "Ks" => material.specular = parse_color(line, rest)?,
+        "Ns" => material.shininess = parse_floats::<1>(line, rest, 1, "1")?[0],
+        "d" => material.dissolve = parse_floats::<1>(line, rest, 1, "1")?[0],
+        "Tr" => material.dissolve = 1.0 - parse_floats::<1>(line, rest, 1, "1")?[0],
+        "illum" => material.illumination = rest.parse::<u32>().map_err(|_| ObjError::BadNumber(line.locate(rest)))?,
+        _ => {
+            // other statements are vendor extensions we have no use for
+            if let Some(map) = TextureMap::from_keyword(keyword) {
+                material.maps.insert(map, dir.join(map_file_name(rest)));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn parse<R: BufRead>(reader: R, filename: &str, mode: ParseMode, report: &mut LoadReport) -> Result<Vec<MaterialData>, ObjError> {
+
+    let dir = Path::new(filename).parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+    let mut materials = Vec::new();
+    let mut reader = LineReader::new(reader);
+    let mut text = String::new();
+    while let Some(number) = reader.read_line(&mut text).map_err(|source| ObjError::Io { file: filename.to_string(), source })? {
+
+        let line = Line { file: filename, number, text: &text };
+        if let Err(err) = parse_line(&line, &dir, &mut materials) {
+            mode.problem(err, report)?;
+        }
+    }
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(source: &str) -> Vec<MaterialData> {
+        parse(source.as_bytes(), "models/test.mtl", ParseMode::Strict, &mut LoadReport::default()).unwrap()
+    }
+
+    #[test]
+    fn colours_take_one_or_three_values() {
+
+        let materials = parse_str("newmtl red\nKa 0.1 0 0\nKd 1 0 0\nKs 0.5\nNs 96\nillum 1\nnewmtl plain\n");
+        let red = &materials[0];
+        assert_eq!((red.ambient, red.diffuse, red.specular), ([0.1, 0.0, 0.0], [1.0, 0.0, 0.0], [0.5; 3]));
+        assert_eq!((red.shininess, red.illumination), (96.0, 1));
+        assert_eq!(materials[1], MaterialData::new("plain"));
+        assert_eq!(materials[1].ambient, [0.2; 3]);
+    }
+
+    #[test]
+    fn the_last_of_d_and_tr_sets_the_dissolve() {
+
+        let materials = parse_str("newmtl glass\nd 0.25\nnewmtl film\nTr 0.25\nnewmtl both\nd 0.25\nTr 0.5\n");
+        let dissolves = materials.iter().map(|material| material.dissolve).collect::<Vec<_>>();
+        assert_eq!(dissolves, vec![0.25, 0.75, 0.5]);
+    }
+
+    #[test]
+    fn map_options_are_skipped_before_the_file_name() {
+
+        assert_eq!(map_file_name("wood.png"), "wood.png");
+        assert_eq!(map_file_name("-s 1 1 1 -bm 0.5 file.png"), "file.png");
+        // -o takes up to three numbers, so a file name after one of them ends the option
+        assert_eq!(map_file_name("-o 0.5 file.png"), "file.png");
+        assert_eq!(map_file_name("-clamp on -mm 0 1 my texture.png"), "my texture.png");
+        // unknown options are taken as part of the name
+        assert_eq!(map_file_name("-unknown file.png"), "-unknown file.png");
+
+        let materials = parse_str("newmtl wood\nmap_Kd -s 1 1 1 -bm 0.5 file.png\nbump -bm 2 normals.png\n");
+        assert_eq!(materials[0].maps.get(&TextureMap::Diffuse), Some(&PathBuf::from("models/file.png")));
+        assert_eq!(materials[0].maps.get(&TextureMap::Bump), Some(&PathBuf::from("models/normals.png")));
+    }
+
+    #[test]
+    fn malformed_statements_are_located() {
+
+        let mut report = LoadReport::default();
+        let materials = parse("newmtl red\nKd 1 x 0\nillum two\n".as_bytes(), "test.mtl", ParseMode::Lenient, &mut report).unwrap();
+        assert_eq!(materials.len(), 1);
+        let warnings = report.warnings.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(warnings, vec!["test.mtl:2:6: bad number `x`", "test.mtl:3:7: bad number `two`"]);
+    }
+}
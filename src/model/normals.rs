@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use cgmath::InnerSpace;
+
+use super::MeshData;
+
+// smoothing group of faces that are always shaded flat (`s off` / `s 0`)
+pub const FLAT: u32 = 0;
+
+// generates one normal per triangle corner by averaging the normals of the faces around
+// each position, weighted by face area and by the angle of the face at that corner.
+// faces only smooth into each other when they share a smoothing group and meet at less
+// than `crease_angle`, so hard edges keep separate normals on each side
+pub fn generate(positions: &[[f32; 3]], triangles: &[[u32; 3]], groups: &[u32], crease_angle: cgmath::Rad<f32>) -> Vec<[f32; 3]> {
+
+    let min_cos = crease_angle.0.cos();
+
+    // area weighted (unnormalized) and unit face normals, plus each corner's angle
+    let mut face_normals = Vec::with_capacity(triangles.len());
+    let mut corner_weights = Vec::with_capacity(triangles.len() * 3);
+    for triangle in triangles {
+        let [a, b, c] = triangle.map(|i| cgmath::Vector3::from(positions[i as usize]));
+        let area_normal = (b - a).cross(c - a);
+        let area = area_normal.magnitude();
+        face_normals.push(if area > 0.0 { area_normal / area } else { area_normal });
+        corner_weights.push(area * corner_angle(a, b, c));
+        corner_weights.push(area * corner_angle(b, c, a));
+        corner_weights.push(area * corner_angle(c, a, b));
+    }
+
+    // corners grouped by the position they reference
+    let mut offsets = vec![0usize; positions.len() + 1];
+    for &position in triangles.iter().flatten() {
+        offsets[position as usize + 1] += 1;
+    }
+    for i in 1..offsets.len() {
+        offsets[i] += offsets[i - 1];
+    }
+    let mut fill = offsets.clone();
+    let mut corners_at = vec![0usize; triangles.len() * 3];
+    for (corner, &position) in triangles.iter().flatten().enumerate() {
+        corners_at[fill[position as usize]] = corner;
+        fill[position as usize] += 1;
+    }
+
+    let mut normals = Vec::with_capacity(triangles.len() * 3);
+    for (corner, &position) in triangles.iter().flatten().enumerate() {
+
+        let face = corner / 3;
+        let face_normal = face_normals[face];
+        if groups[face] == FLAT {
+            normals.push(face_normal.into());
+            continue;
+        }
+
+        let degenerate = face_normal.magnitude2() == 0.0;
+        let position = position as usize;
+        let mut normal = cgmath::Vector3::new(0.0, 0.0, 0.0);
+        for &other in &corners_at[offsets[position]..offsets[position + 1]] {
+            let other_face = other / 3;
+            if groups[other_face] != groups[face] {
+                continue;
+            }
+            if !degenerate && face_normal.dot(face_normals[other_face]) < min_cos {
+                continue;
+            }
+            normal += face_normals[other_face] * corner_weights[other];
+        }
+
+        normals.push(if normal.magnitude2() > 0.0 {
+            normal.normalize().into()
+        } else if !degenerate {
+            face_normal.into()
+        } else {
+            [0.0, 1.0, 0.0]
+        });
+    }
+    normals
+}
+
+fn corner_angle(corner: cgmath::Vector3<f32>, next: cgmath::Vector3<f32>, prev: cgmath::Vector3<f32>) -> f32 {
+
+    let (u, v) = (next - corner, prev - corner);
+    if u.magnitude2() == 0.0 || v.magnitude2() == 0.0 {
+        return 0.0;
+    }
+    u.angle(v).0
+}
+
+// gives a mesh without normals the ones `generate` finds with all its faces in one smoothing
+// group. vertices keep their place with the normal of the first corner using them, a copy is
+// added for every other normal. vertices only lines and points use get none, they are unlit
+pub fn fill(mesh: &mut MeshData, crease_angle: cgmath::Rad<f32>) {
+
+    let triangles = mesh.indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect::<Vec<_>>();
+    let generated = generate(&mesh.positions, &triangles, &vec![1; triangles.len()], crease_angle);
+    let mut used = vec![false; mesh.positions.len()];
+    mesh.normals = vec![[0.0; 3]; mesh.positions.len()];
+    let mut copies: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+
+    for (index, normal) in mesh.indices.iter_mut().zip(generated) {
+        let vertex = *index as usize;
+        if !used[vertex] {
+            used[vertex] = true;
+            mesh.normals[vertex] = normal;
+        } else if mesh.normals[vertex] != normal {
+            *index = *copies.entry((*index, normal.map(f32::to_bits))).or_insert_with(|| {
+                mesh.positions.push(mesh.positions[vertex]);
+                mesh.normals.push(normal);
+                if !mesh.texcoords.is_empty() {
+                    mesh.texcoords.push(mesh.texcoords[vertex]);
+                }
+                if !mesh.colors.is_empty() {
+                    mesh.colors.push(mesh.colors[vertex]);
+                }
+                if !mesh.tangents.is_empty() {
+                    mesh.tangents.push(mesh.tangents[vertex]);
+                }
+                for scalar in &mut mesh.scalars {
+                    scalar.values.push(scalar.values[vertex]);
+                }
+                (mesh.positions.len() - 1) as u32
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a unit cube centred on the origin, two outward facing triangles on each side
+    fn cube() -> (Vec<[f32; 3]>, Vec<[u32; 3]>) {
+
+        let positions = (0..8).map(|i| [(i & 1) as f32 - 0.5, ((i >> 1) & 1) as f32 - 0.5, ((i >> 2) & 1) as f32 - 0.5]).collect();
+        let quads = [[0, 2, 3, 1], [4, 5, 7, 6], [0, 1, 5, 4], [2, 6, 7, 3], [0, 4, 6, 2], [1, 3, 7, 5]];
+        let triangles = quads.iter().flat_map(|&[a, b, c, d]| [[a, b, c], [a, c, d]]).collect();
+        (positions, triangles)
+    }
+
+    fn close(a: [f32; 3], b: [f32; 3]) -> bool {
+        a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5)
+    }
+
+    #[test]
+    fn cube_edges_are_hard_below_the_crease_angle_and_smooth_above_it() {
+
+        let (positions, triangles) = cube();
+        let groups = vec![1; triangles.len()];
+
+        // every corner keeps the normal of its side
+        let hard = generate(&positions, &triangles, &groups, cgmath::Deg(60.0).into());
+        for (corner, normal) in hard.iter().enumerate() {
+            let [a, b, c] = triangles[corner / 3].map(|i| cgmath::Vector3::from(positions[i as usize]));
+            assert!(close(*normal, (b - a).cross(c - a).normalize().into()), "{:?}", normal);
+        }
+
+        // every corner at a cube corner shares a normal pointing out of it
+        let smooth = generate(&positions, &triangles, &groups, cgmath::Deg(180.0).into());
+        for (corner, normal) in smooth.iter().enumerate() {
+            let position = cgmath::Vector3::from(positions[triangles[corner / 3][corner % 3] as usize]);
+            assert!(close(*normal, position.normalize().into()), "{:?} at {:?}", normal, position);
+        }
+    }
+
+    #[test]
+    fn flat_faces_and_other_smoothing_groups_are_not_smoothed_into() {
+
+        let (positions, triangles) = cube();
+        let flat = generate(&positions, &triangles, &vec![FLAT; triangles.len()], cgmath::Deg(180.0).into());
+        let hard = generate(&positions, &triangles, &vec![1; triangles.len()], cgmath::Deg(60.0).into());
+        assert_eq!(flat, hard);
+
+        // each side in a group of its own
+        let groups = (0..triangles.len() as u32).map(|face| face / 2 + 1).collect::<Vec<_>>();
+        assert_eq!(generate(&positions, &triangles, &groups, cgmath::Deg(180.0).into()), hard);
+    }
+
+    #[test]
+    fn large_faces_outweigh_slivers() {
+
+        // a large triangle facing +z and a thin one facing +x, sharing the corner at the origin
+        let positions = [[0.0, 0.0, 0.0], [4.0, 0.0, 0.0], [0.0, 4.0, 0.0], [0.0, 0.1, 0.0], [0.0, 0.0, 0.1]];
+        let triangles = [[0, 1, 2], [0, 3, 4]];
+        let normals = generate(&positions, &triangles, &[1, 1], cgmath::Deg(180.0).into());
+        let normal = cgmath::Vector3::from(normals[0]);
+        assert!(normal.z > 0.99, "{:?}", normal);
+        assert!(normal.x > 0.0);
+        assert_eq!(normals[0], normals[3]);
+    }
+}
@@ -0,0 +1,827 @@
+use std::{borrow::Cow, collections::HashMap, path::{Path, PathBuf}, sync::{atomic::{AtomicUsize, Ordering}, Arc}};
+
+use rayon::prelude::*;
+
+use super::error::{LoadReport, ObjError, ParseMode};
+use super::files::FileSystem;
+use super::mtl::{self, MaterialData};
+use super::bounds::Aabb;
+use super::tokenizer::{parse_floats, split_chunks, Line, Lines};
+use super::{normals, triangulate};
+use super::{LoadOptions, MaterialRange, MeshData, Submesh};
+
+// valid OBJ statements that don't contribute to the mesh we build
+const IGNORED_DIRECTIVES: &[&str] = &["vp"];
+
+// name of the submesh holding faces that come before any `o` or `g`
+const DEFAULT_SUBMESH: &str = "default";
+
+// files are split into chunks of about this many bytes, which are parsed on separate threads
+const CHUNK_SIZE: usize = 1 << 20;
+
+// number of positions, texcoords and normals defined before some line
+#[derive(Copy, Clone, Debug, Default)]
+struct Counts {
+    positions: usize,
+    texcoords: usize,
+    normals: usize
+}
+
+// a single corner of a face, with zero-based indices into the position, texcoord and normal lists
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct FaceVertex {
+    position: u32,
+    texcoord: Option<u32>,
+    normal: Option<u32>
+}
+
+impl FaceVertex {
+
+    // accepts the four reference forms: v, v/vt, v//vn and v/vt/vn
+    fn parse(line: &Line, token: &str, counts: &Counts) -> Result<Self, ObjError> {
+
+        let mut parts = token.split('/');
+        let position = match parts.next() {
+            Some(s) if !s.is_empty() => Self::parse_index(line, s, counts.positions)?,
+            _ => return Err(ObjError::BadNumber(line.locate(token)))
+        };
+        let texcoord = match parts.next() {
+            Some("") | None => None,
+            Some(s) => Some(Self::parse_index(line, s, counts.texcoords)?)
+        };
+        let normal = match parts.next() {
+            Some("") | None => None,
+            Some(s) => Some(Self::parse_index(line, s, counts.normals)?)
+        };
+        if parts.next().is_some() {
+            return Err(ObjError::BadNumber(line.locate(token)));
+        }
+        Ok(Self {
+            position,
+            texcoord,
+            normal
+        })
+    }
+
+    // positive indices count from the start of the file, negative ones back from the
+    // last element defined before this line
+    fn parse_index(line: &Line, s: &str, count: usize) -> Result<u32, ObjError> {
+
+        let index = s.parse::<i64>().map_err(|_| ObjError::BadNumber(line.locate(s)))?;
+        let resolved = match index {
+            0 => return Err(ObjError::ZeroIndex(line.locate(s))),
+            i if i > 0 => i - 1,
+            i => count as i64 + i
+        };
+        if resolved < 0 || resolved >= count as i64 {
+            return Err(ObjError::IndexOutOfRange { location: line.locate(s), count });
+        }
+        Ok(resolved as u32)
+    }
+
+    fn has_references(&self) -> bool {
+        self.texcoord.is_some() || self.normal.is_some()
+    }
+}
+
+// a line that can only be handled once the elements of the chunks before it are known
+struct Deferred<'a> {
+    number: usize,
+    text: Cow<'a, str>,
+    // elements defined before it, counting from the start of its chunk
+    counts: Counts
+}
+
+// what a chunk of the file defines. positions, texcoords and normals only depend on their own
+// line, everything else is kept for later
+#[derive(Default)]
+struct Chunk<'a> {
+    positions: Vec<[f32; 3]>,
+    // colour of each position, white for the ones written without one
+    colors: Vec<[f32; 3]>,
+    colored: bool,
+    normals: Vec<[f32; 3]>,
+    texcoords: Vec<[f32; 2]>,
+    deferred: Vec<Deferred<'a>>,
+    // errors along with the line they were found on
+    errors: Vec<(usize, ObjError)>
+}
+
+impl<'a> Chunk<'a> {
+
+    fn parse(text: &'a str, first_line: usize, filename: &str) -> Self {
+
+        let mut chunk = Self::default();
+        for (number, text) in Lines::new(text, first_line) {
+
+            let line = Line { file: filename, number, text: &text };
+            let Some((keyword, rest)) = line.split_keyword() else {
+                continue;
+            };
+            let result = match keyword {
+                "v" => chunk.parse_position(&line, rest),
+                "vn" => parse_floats::<3>(&line, rest, 3, "3").map(|normal| chunk.normals.push(normal)),
+                "vt" => {
+                    // the optional w component is ignored
+                    parse_floats::<3>(&line, rest, 1, "1 to 3").map(|[u, v, _]| chunk.texcoords.push([u, v]))
+                },
+                _ => {
+                    let counts = chunk.counts();
+                    chunk.deferred.push(Deferred { number, text, counts });
+                    continue;
+                }
+            };
+            if let Err(err) = result {
+                chunk.errors.push((number, err));
+            }
+        }
+        chunk
+    }
+
+    fn parse_position(&mut self, line: &Line, rest: &str) -> Result<(), ObjError> {
+
+        // either an optional w component, which is ignored, or the r g b colour extension
+        let [x, y, z, r, g, b] = parse_floats::<6>(line, rest, 3, "3, 4 or 6")?;
+        let color = match rest.split_whitespace().count() {
+            3 | 4 => [1.0; 3],
+            6 => {
+                self.colored = true;
+                [r, g, b]
+            },
+            _ => return Err(ObjError::WrongComponentCount { location: line.locate(rest), expected: "3, 4 or 6" })
+        };
+        self.positions.push([x, y, z]);
+        self.colors.push(color);
+        Ok(())
+    }
+
+    fn counts(&self) -> Counts {
+        Counts {
+            positions: self.positions.len(),
+            texcoords: self.texcoords.len(),
+            normals: self.normals.len()
+        }
+    }
+}
+
+// the elements added by an `f`, `l` or `p` statement, with their references resolved
+enum Element {
+    Triangles(Vec<[FaceVertex; 3]>),
+    Segments(Vec<[FaceVertex; 2]>),
+    Points(Vec<FaceVertex>)
+}
+
+impl Element {
+
+    // `counts` are the elements defined before the line in the whole file, `positions` all of them
+    fn parse(line: &Line, counts: &Counts, positions: &[[f32; 3]]) -> Result<Option<Self>, ObjError> {
+
+        let Some((keyword, rest)) = line.split_keyword() else {
+            return Ok(None);
+        };
+        let refs = || rest.split_whitespace()
+            .map(|token| FaceVertex::parse(line, token, counts))
+            .collect::<Result<Vec<FaceVertex>, ObjError>>();
+        let element = match keyword {
+            "f" => {
+                let refs = refs()?;
+                if refs.len() < 3 {
+                    return Err(ObjError::TooFewVertices(line.locate(rest)));
+                }
+                let polygon = refs.iter().map(|fv| positions[fv.position as usize]).collect::<Vec<[f32; 3]>>();
+                Element::Triangles(triangulate::triangulate(&polygon).into_iter().map(|[a, b, c]| [refs[a], refs[b], refs[c]]).collect())
+            },
+            "l" => {
+                let refs = refs()?;
+                if refs.len() < 2 {
+                    return Err(ObjError::TooFewVertices(line.locate(rest)));
+                }
+                Element::Segments(refs.windows(2).map(|segment| [segment[0], segment[1]]).collect())
+            },
+            "p" => {
+                let refs = refs()?;
+                if refs.is_empty() {
+                    return Err(ObjError::TooFewVertices(line.locate(rest)));
+                }
+                Element::Points(refs)
+            },
+            _ => return Ok(None)
+        };
+        Ok(Some(element))
+    }
+}
+
+struct ObjParser {
+    // directory `mtllib` paths are relative to
+    dir: PathBuf,
+    files: Arc<dyn FileSystem>,
+    mode: ParseMode,
+    vertices: Vec<[f32; 3]>,
+    vertex_colors: Vec<[f32; 3]>,
+    colored_vertices: bool,
+    vertex_normals: Vec<[f32; 3]>,
+    vertex_texcoords: Vec<[f32; 2]>,
+    faces: Vec<[FaceVertex; 3]>,
+    // segments of the `l` polylines and the vertices of `p` statements
+    lines: Vec<[FaceVertex; 2]>,
+    points: Vec<FaceVertex>,
+    // smoothing group of each face, all faces share one group unless the file uses `s`
+    face_groups: Vec<u32>,
+    smoothing_group: u32,
+    materials: Vec<MaterialData>,
+    // material of each face, None for faces before any `usemtl`
+    face_materials: Vec<Option<usize>>,
+    material: Option<usize>,
+    // names of the `o` and `g` submeshes, and the one each face belongs to
+    submesh_names: Vec<String>,
+    face_submeshes: Vec<usize>,
+    object: Option<String>,
+    group: Option<String>,
+    submesh: Option<usize>,
+    indexed_references: bool
+}
+
+impl ObjParser {
+
+    fn new(filename: &str, options: &LoadOptions) -> Self {
+        Self {
+            dir: Path::new(filename).parent().unwrap_or_else(|| Path::new("")).to_path_buf(),
+            files: options.files.clone(),
+            mode: options.mode,
+            vertices: Vec::new(),
+            vertex_colors: Vec::new(),
+            colored_vertices: false,
+            vertex_normals: Vec::new(),
+            vertex_texcoords: Vec::new(),
+            faces: Vec::new(),
+            lines: Vec::new(),
+            points: Vec::new(),
+            face_groups: Vec::new(),
+            smoothing_group: 1,
+            materials: Vec::new(),
+            face_materials: Vec::new(),
+            material: None,
+            submesh_names: Vec::new(),
+            face_submeshes: Vec::new(),
+            object: None,
+            group: None,
+            submesh: None,
+            indexed_references: false
+        }
+    }
+
+    // appends the vertex data of a chunk, returning the counts its deferred lines are relative to
+    fn append(&mut self, chunk: &mut Chunk) -> Counts {
+
+        let base = Counts {
+            positions: self.vertices.len(),
+            texcoords: self.vertex_texcoords.len(),
+            normals: self.vertex_normals.len()
+        };
+        self.vertices.append(&mut chunk.positions);
+        self.vertex_colors.append(&mut chunk.colors);
+        self.colored_vertices |= chunk.colored;
+        self.vertex_normals.append(&mut chunk.normals);
+        self.vertex_texcoords.append(&mut chunk.texcoords);
+        base
+    }
+
+    // applies a deferred line in file order, `element` is what it adds if it is an `f`, `l` or `p`
+    fn apply(&mut self, line: &Line, element: Option<Element>, report: &mut LoadReport) -> Result<(), ObjError> {
+
+        match element {
+            Some(Element::Triangles(triangles)) => {
+                self.indexed_references |= triangles.iter().flatten().any(FaceVertex::has_references);
+                let submesh = self.current_submesh();
+                for triangle in triangles {
+                    self.faces.push(triangle);
+                    self.face_groups.push(self.smoothing_group);
+                    self.face_materials.push(self.material);
+                    self.face_submeshes.push(submesh);
+                }
+                return Ok(());
+            },
+            Some(Element::Segments(segments)) => {
+                self.lines.extend(segments);
+                return Ok(());
+            },
+            Some(Element::Points(points)) => {
+                self.points.extend(points);
+                return Ok(());
+            },
+            None => ()
+        }
+
+        let Some((keyword, rest)) = line.split_keyword() else {
+            return Ok(());
+        };
+        match keyword {
+            "s" => {
+                self.smoothing_group = match rest {
+                    "off" => normals::FLAT,
+                    _ => rest.parse::<u32>().map_err(|_| ObjError::BadNumber(line.locate(rest)))?
+                };
+            },
+            "mtllib" => {
+                for library in rest.split_whitespace() {
+                    self.load_library(library, report)?;
+                }
+            },
+            "o" => {
+                // a new object starts outside of any group
+                self.object = Some(rest.to_string()).filter(|name| !name.is_empty());
+                self.group = None;
+                self.submesh = None;
+            },
+            "g" => {
+                // faces in several groups at once form a submesh of their own
+                let names = rest.split_whitespace().collect::<Vec<&str>>();
+                self.group = Some(names.join(" ")).filter(|name| !name.is_empty());
+                self.submesh = None;
+            },
+            "usemtl" => {
+                // faces after an unknown material fall back to the default one
+                self.material = self.materials.iter().rposition(|m| m.name == rest);
+                if self.material.is_none() {
+                    return Err(ObjError::UnknownMaterial(line.locate(rest)));
+                }
+            },
+            _ if IGNORED_DIRECTIVES.contains(&keyword) => (),
+            _ => return Err(ObjError::UnsupportedDirective(line.locate(keyword)))
+        }
+        Ok(())
+    }
+
+    fn load_library(&mut self, library: &str, report: &mut LoadReport) -> Result<(), ObjError> {
+
+        let path = self.dir.join(library);
+        let filename = path.to_string_lossy().to_string();
+        // recorded even when missing, the cache has to notice it being added later
+        report.libraries.push(path.clone());
+        let bytes = self.files.read(&path).map_err(|source| ObjError::Io { file: filename.clone(), source })?;
+        let materials = mtl::parse(&bytes[..], &filename, self.mode, report)?;
+        self.materials.extend(materials);
+        Ok(())
+    }
+
+    // submeshes are looked up lazily, so `o` and `g` statements without faces don't create empty ones
+    fn current_submesh(&mut self) -> usize {
+
+        if let Some(submesh) = self.submesh {
+            return submesh;
+        }
+        let name = match (&self.object, &self.group) {
+            (Some(object), Some(group)) => format!("{}/{}", object, group),
+            (Some(name), None) | (None, Some(name)) => name.clone(),
+            (None, None) => DEFAULT_SUBMESH.to_string()
+        };
+        let submesh = match self.submesh_names.iter().position(|existing| *existing == name) {
+            Some(submesh) => submesh,
+            None => {
+                self.submesh_names.push(name);
+                self.submesh_names.len() - 1
+            }
+        };
+        self.submesh = Some(submesh);
+        submesh
+    }
+
+    fn build(self, options: &LoadOptions) -> MeshData {
+
+        // files without face references may still list one normal per position
+        let normals_per_position = !self.indexed_references && !self.vertex_normals.is_empty() && self.vertex_normals.len() == self.vertices.len();
+
+        // corners that reference no normal get a smooth one generated from the faces around them
+        let generated_normals = if !normals_per_position && self.faces.iter().flatten().any(|fv| fv.normal.is_none()) {
+            let triangles = self.faces.iter().map(|face| face.map(|fv| fv.position)).collect::<Vec<[u32; 3]>>();
+            normals::generate(&self.vertices, &triangles, &self.face_groups, options.crease_angle.into())
+        } else {
+            Vec::new()
+        };
+
+        // faces are ordered by submesh and then by material so each submesh is a single range
+        // and each material inside it too, faces without a material go to the default one at
+        // the end of the list
+        let mut materials = self.materials;
+        let default_material = materials.len();
+        if self.face_materials.iter().any(Option::is_none) {
+            materials.push(MaterialData::default());
+        }
+        let mut face_order = (0..self.faces.len()).collect::<Vec<usize>>();
+        face_order.sort_by_key(|&face| (self.face_submeshes[face], self.face_materials[face].unwrap_or(default_material)));
+
+        // every distinct (position, texcoord, normal) combination becomes one vertex
+        let mut unique_vertices : HashMap<(u32, Option<u32>, [u32; 3]), u32> = HashMap::new();
+        let mut positions : Vec<[f32; 3]> = Vec::with_capacity(self.vertices.len());
+        let mut normals : Vec<[f32; 3]> = Vec::with_capacity(self.vertices.len());
+        let mut texcoords : Vec<[f32; 2]> = Vec::new();
+        let mut colors : Vec<[f32; 3]> = Vec::new();
+        let mut vertex = |fv: &FaceVertex, normal: [f32; 3]| {
+            let key = (fv.position, fv.texcoord, normal.map(f32::to_bits));
+            *unique_vertices.entry(key).or_insert_with(|| {
+                positions.push(self.vertices[fv.position as usize]);
+                normals.push(normal);
+                // texcoords are only kept when the file has any, vertices without one get (0, 0)
+                if !self.vertex_texcoords.is_empty() {
+                    texcoords.push(fv.texcoord.map_or([0.0; 2], |t| self.vertex_texcoords[t as usize]));
+                }
+                if self.colored_vertices {
+                    colors.push(self.vertex_colors[fv.position as usize]);
+                }
+                (positions.len() - 1) as u32
+            })
+        };
+
+        let mut indices : Vec<u32> = Vec::with_capacity(self.faces.len() * 3);
+        let mut submeshes : Vec<Submesh> = Vec::new();
+        let mut submesh_ids : Vec<usize> = Vec::new();
+        for face in face_order {
+
+            let start = indices.len() as u32;
+            if submesh_ids.last() != Some(&self.face_submeshes[face]) {
+                submesh_ids.push(self.face_submeshes[face]);
+                submeshes.push(Submesh {
+                    name: self.submesh_names[self.face_submeshes[face]].clone(),
+                    indices: start..start,
+                    material_ranges: Vec::new(),
+                    bounds: Aabb::empty()
+                });
+            }
+            let submesh = submeshes.last_mut().unwrap();
+            submesh.indices.end += 3;
+
+            let material = self.face_materials[face].unwrap_or(default_material);
+            match submesh.material_ranges.last_mut() {
+                Some(range) if range.material == material => range.indices.end += 3,
+                _ => submesh.material_ranges.push(MaterialRange { material, indices: start..start + 3 })
+            }
+
+            for (k, fv) in self.faces[face].iter().enumerate() {
+                let normal = match fv.normal {
+                    Some(n) => self.vertex_normals[n as usize],
+                    None if normals_per_position => self.vertex_normals[fv.position as usize],
+                    None => generated_normals[face * 3 + k]
+                };
+                indices.push(vertex(fv, normal));
+                submesh.bounds.grow(self.vertices[fv.position as usize]);
+            }
+        }
+
+        // lines and points are drawn unlit, so they only keep the normals the file gives them
+        let mut unlit_vertex = |fv: &FaceVertex| {
+            let normal = match fv.normal {
+                Some(n) => self.vertex_normals[n as usize],
+                None if normals_per_position => self.vertex_normals[fv.position as usize],
+                None => [0.0; 3]
+            };
+            vertex(fv, normal)
+        };
+        let line_indices = self.lines.iter().flatten().map(&mut unlit_vertex).collect::<Vec<u32>>();
+        let point_indices = self.points.iter().map(&mut unlit_vertex).collect::<Vec<u32>>();
+
+        MeshData {
+            positions,
+            normals,
+            texcoords,
+            colors,
+            tangents: Vec::new(),
+            scalars: Vec::new(),
+            indices,
+            line_indices,
+            point_indices,
+            submeshes,
+            materials
+        }
+    }
+}
+
+// parses a whole file. it is split into chunks whose positions, texcoords and normals are
+// parsed in parallel, then the faces, lines and points of every chunk are resolved in parallel
+// against all of them, and finally the remaining statements are applied in file order.
+// `progress` is called with the fraction of the work done so far
+pub fn parse(bytes: &[u8], filename: &str, options: &LoadOptions, progress: &(dyn Fn(f32) + Sync)) -> Result<(MeshData, LoadReport), ObjError> {
+    parse_chunks(bytes, filename, options, CHUNK_SIZE, progress)
+}
+
+fn parse_chunks(bytes: &[u8], filename: &str, options: &LoadOptions, chunk_size: usize, progress: &(dyn Fn(f32) + Sync)) -> Result<(MeshData, LoadReport), ObjError> {
+
+    let pieces = split_chunks(bytes, chunk_size);
+    let texts = pieces.par_iter()
+        .map(|piece| std::str::from_utf8(piece))
+        .collect::<Result<Vec<&str>, _>>()
+        .map_err(|err| ObjError::Io { file: filename.to_string(), source: std::io::Error::new(std::io::ErrorKind::InvalidData, err) })?;
+    let line_counts = texts.par_iter().map(|text| text.matches('\n').count()).collect::<Vec<usize>>();
+    let first_lines = line_counts.iter().scan(1, |next, count| {
+        let first = *next;
+        *next += count;
+        Some(first)
+    }).collect::<Vec<usize>>();
+
+    // every byte is looked at once in each of the two parallel passes
+    let work = (bytes.len() * 2).max(1) as f32;
+    let done = AtomicUsize::new(0);
+    let advance = |len: usize| {
+        let done = done.fetch_add(len, Ordering::Relaxed) + len;
+        progress(done as f32 / work);
+    };
+
+    let mut chunks = texts.par_iter().zip(first_lines.par_iter())
+        .map(|(text, &first_line)| {
+            let chunk = Chunk::parse(text, first_line, filename);
+            advance(text.len());
+            chunk
+        })
+        .collect::<Vec<Chunk>>();
+
+    let mut parser = ObjParser::new(filename, options);
+    let bases = chunks.iter_mut().map(|chunk| parser.append(chunk)).collect::<Vec<Counts>>();
+
+    let elements = chunks.par_iter().zip(bases.par_iter()).zip(texts.par_iter())
+        .map(|((chunk, base), text)| {
+            let elements = chunk.deferred.iter().map(|deferred| {
+                let line = Line { file: filename, number: deferred.number, text: &deferred.text };
+                let counts = Counts {
+                    positions: base.positions + deferred.counts.positions,
+                    texcoords: base.texcoords + deferred.counts.texcoords,
+                    normals: base.normals + deferred.counts.normals
+                };
+                Element::parse(&line, &counts, &parser.vertices)
+            }).collect::<Vec<_>>();
+            advance(text.len());
+            elements
+        })
+        .collect::<Vec<_>>();
+
+    let mut report = LoadReport::default();
+    let mut errors = Vec::new();
+    for (chunk, elements) in chunks.iter_mut().zip(elements) {
+        errors.append(&mut chunk.errors);
+        for (deferred, element) in chunk.deferred.iter().zip(elements) {
+            let line = Line { file: filename, number: deferred.number, text: &deferred.text };
+            // warnings of material libraries are reported at the `mtllib` line that loaded them
+            let mut library_report = LoadReport::default();
+            let result = element.and_then(|element| parser.apply(&line, element, &mut library_report));
+            errors.extend(library_report.warnings.into_iter().map(|warning| (deferred.number, warning)));
+            report.libraries.append(&mut library_report.libraries);
+            if let Err(err) = result {
+                errors.push((deferred.number, err));
+            }
+        }
+    }
+
+    // the passes find errors out of order, so they are sorted back by line
+    errors.sort_by_key(|&(number, _)| number);
+    for (_, err) in errors {
+        options.mode.problem(err, &mut report)?;
+    }
+
+    // positions no element refers to never become vertices, so welding can't count them
+    let mut referenced = vec![false; parser.vertices.len()];
+    for fv in parser.faces.iter().flatten().chain(parser.lines.iter().flatten()).chain(&parser.points) {
+        referenced[fv.position as usize] = true;
+    }
+    report.weld.unreferenced = referenced.iter().filter(|&&r| !r).count();
+
+    let data = parser.build(options);
+    progress(1.0);
+    Ok((data, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(source: &str) -> Result<MeshData, ObjError> {
+        let options = LoadOptions {
+            mode: ParseMode::Strict,
+            ..LoadOptions::default()
+        };
+        parse(source.as_bytes(), "test.obj", &options, &|_| ()).map(|(data, _)| data)
+    }
+
+    fn positions(data: &MeshData) -> Vec<[f32; 3]> {
+        data.indices.iter().map(|&i| data.positions[i as usize]).collect()
+    }
+
+    #[test]
+    fn every_face_reference_form_is_read() {
+
+        let vertices = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\n";
+        let plain = parse_str(&format!("{}f 1 2 3\n", vertices)).unwrap();
+        let textured = parse_str(&format!("{}f 1/1 2/2 3/3\n", vertices)).unwrap();
+        let normal = parse_str(&format!("{}f 1//1 2//1 3//1\n", vertices)).unwrap();
+        let full = parse_str(&format!("{}f 1/1/1 2/2/1 3/3/1\n", vertices)).unwrap();
+        for data in [&plain, &textured, &normal, &full] {
+            assert_eq!(positions(data), vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+        }
+        for data in [&normal, &full] {
+            assert!(data.normals.iter().all(|&normal| normal == [0.0, 0.0, 1.0]));
+        }
+    }
+
+    #[test]
+    fn corners_sharing_a_position_but_not_a_texcoord_are_separate_vertices() {
+
+        let data = parse_str("\
+            v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\n\
+            vt 0 0\nvt 1 0\nvt 0 1\nvt 1 1\nvt 0.5 0.5\n\
+            f 1/1 2/2 3/3\nf 2/2 4/4 3/5\n").unwrap();
+        // the second face reuses corner 2/2 but not 3/3
+        assert_eq!(data.positions.len(), 5);
+        assert_eq!(data.indices[3], data.indices[1]);
+        assert_ne!(data.indices[5], data.indices[2]);
+    }
+
+    #[test]
+    fn lenient_mode_skips_malformed_lines_and_reports_them() {
+
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 x 0\nf 1 2 3\nf 1 2\n";
+        let (data, report) = parse(source.as_bytes(), "test.obj", &LoadOptions::default(), &|_| ()).unwrap();
+        assert_eq!(positions(&data), vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+        let warnings = report.warnings.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(warnings, vec![
+            "test.obj:4:5: bad number `x`",
+            "test.obj:6:3: face `1 2` has fewer than 3 vertices"
+        ]);
+        // the same file fails a strict load at its first malformed line
+        assert_eq!(parse_str(source).err().unwrap().to_string(), warnings[0]);
+    }
+
+    #[test]
+    fn errors_point_at_the_column_of_their_text() {
+
+        // columns count from 1
+        let err = parse_str("v 0 0 0\nv 1 0 0\nvn 0 1\n").err().unwrap();
+        match &err {
+            ObjError::WrongComponentCount { location, .. } => assert_eq!((location.line, location.column, location.text.as_str()), (3, 4, "0 1")),
+            other => panic!("unexpected error {}", other)
+        }
+        assert_eq!(err.to_string(), "test.obj:3:4: expected 3 components in `0 1`");
+        assert!(matches!(parse_str("v 0 0 0\nv 1 0 0\nf 1 2 0\n"), Err(ObjError::ZeroIndex(_))));
+        assert!(matches!(parse_str("v 0 0 0\nvx 1\n"), Err(ObjError::UnsupportedDirective(_))));
+    }
+
+    #[test]
+    fn relative_indices_match_absolute_ones() {
+
+        let vertices = "v 0 0 0\nv 1 0 0\nv 0 1 0\n";
+        let relative = parse_str(&format!("{}f -3 -2 -1\n", vertices)).unwrap();
+        let absolute = parse_str(&format!("{}f 1 2 3\n", vertices)).unwrap();
+        assert_eq!(positions(&relative), positions(&absolute));
+    }
+
+    #[test]
+    fn relative_indices_resolve_against_elements_defined_so_far() {
+
+        let data = parse_str("\
+            v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+            f -3 -2 -1\n\
+            v 0 0 5\nv 1 0 5\nv 0 1 5\n\
+            f -3 -2 -1\n").unwrap();
+        assert_eq!(positions(&data), vec![
+            [0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0],
+            [0.0, 0.0, 5.0], [1.0, 0.0, 5.0], [0.0, 1.0, 5.0]
+        ]);
+    }
+
+    #[test]
+    fn relative_texcoord_and_normal_indices() {
+
+        let data = parse_str("\
+            v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+            vt 0 0\nvt 1 1\n\
+            vn 0 0 1\nvn 0 0 -1\n\
+            f -3/-2/-2 -2/-2/-2 -1/-1/-1\n").unwrap();
+        let normals = data.indices.iter().map(|&i| data.normals[i as usize]).collect::<Vec<_>>();
+        assert_eq!(normals, vec![[0.0, 0.0, 1.0], [0.0, 0.0, 1.0], [0.0, 0.0, -1.0]]);
+    }
+
+    #[test]
+    fn relative_index_before_first_element_is_out_of_range() {
+
+        match parse_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -4 -2 -1\n") {
+            Err(ObjError::IndexOutOfRange { location, count }) => {
+                assert_eq!((location.line, location.column, location.text.as_str(), count), (4, 3, "-4", 3));
+            },
+            Err(other) => panic!("unexpected error {}", other),
+            Ok(_) => panic!("expected an out of range index")
+        }
+    }
+
+    #[test]
+    fn smoothing_off_gives_flat_normals() {
+
+        // two faces folded by 45 degrees, which the default crease angle would smooth
+        let faces = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 -1 1\nf 1 2 3\nf 2 1 4\n";
+        let normals = |data: &MeshData| data.indices.iter().map(|&i| data.normals[i as usize]).collect::<Vec<_>>();
+        let smooth = normals(&parse_str(&format!("s 1\n{}", faces)).unwrap());
+        assert_ne!(smooth[0], [0.0, 0.0, 1.0]);
+        for off in ["s off", "s 0"] {
+            let flat = normals(&parse_str(&format!("{}\n{}", off, faces)).unwrap());
+            assert_eq!(flat[..3], [[0.0, 0.0, 1.0]; 3]);
+        }
+    }
+
+    #[test]
+    fn objects_and_groups_become_named_submeshes() {
+
+        let data = parse_str("\
+            v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+            f 1 2 3\n\
+            o body\nf 1 2 3\n\
+            g lid\nf 1 2 3\n\
+            o handle\ng\nf 1 2 3\n\
+            o body\nf 1 2 3\n\
+            o empty\n").unwrap();
+        let names = data.submeshes.iter().map(|submesh| submesh.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["default", "body", "body/lid", "handle"]);
+        // faces of a reopened object join its submesh, so each one is a single range
+        let counts = data.submeshes.iter().map(|submesh| submesh.indices.end - submesh.indices.start).collect::<Vec<_>>();
+        assert_eq!(counts, vec![3, 6, 3, 3]);
+        assert_eq!(data.submeshes[1].bounds.min, [0.0, 0.0, 0.0]);
+        assert_eq!(data.submeshes[1].bounds.max, [1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn vertex_colors_are_kept_per_position() {
+
+        let data = parse_str("v 0 0 0 1 0 0\nv 1 0 0\nv 0 1 0 0 0 1\nf 1 2 3\n").unwrap();
+        let colors = data.indices.iter().map(|&i| data.colors[i as usize]).collect::<Vec<_>>();
+        assert_eq!(colors, vec![[1.0, 0.0, 0.0], [1.0, 1.0, 1.0], [0.0, 0.0, 1.0]]);
+        assert!(parse_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap().colors.is_empty());
+    }
+
+    #[test]
+    fn polylines_become_segments_and_points_are_kept() {
+
+        let data = parse_str("v 0 0 0\nv 1 0 0\nv 1 1 0\nl 1 2 3\np 3 1\n").unwrap();
+        let segments = data.line_indices.iter().map(|&i| data.positions[i as usize]).collect::<Vec<_>>();
+        assert_eq!(segments, vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]]);
+        let points = data.point_indices.iter().map(|&i| data.positions[i as usize]).collect::<Vec<_>>();
+        assert_eq!(points, vec![[1.0, 1.0, 0.0], [0.0, 0.0, 0.0]]);
+        assert!(data.indices.is_empty());
+    }
+
+    #[test]
+    fn whitespace_comments_and_continuations_parse_like_plain_lines() {
+
+        let plain = parse_str("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n").unwrap();
+        let messy = parse_str("# exported\r\n  v\t0 0 0\r\nv  1 0 0 # right\r\nv 1 1 0\nv 0 1 0\nf 1 2 \\\r\n 3 4\r\n").unwrap();
+        assert_eq!(positions(&messy), positions(&plain));
+    }
+
+    #[test]
+    fn chunks_of_any_size_parse_like_a_single_one() {
+
+        let source = "\
+            v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\n\
+            o first\ns 1\nf 1//1 2//1 3//1\nf -4 -2 -1\n\
+            v 0 0 1\nv 1 0 1 \\\n\nv 1 1 1\n\
+            g side\ns off\nf -3 -2 -1 4\nl 1 5 6\np -1\n\
+            f 9 9 9\nbogus\n";
+        let options = LoadOptions::default();
+        let (whole, whole_report) = parse_chunks(source.as_bytes(), "test.obj", &options, source.len(), &|_| ()).unwrap();
+        let whole_warnings = whole_report.warnings.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(whole_warnings.len(), 2);
+        for size in 1..source.len() {
+            let (chunked, report) = parse_chunks(source.as_bytes(), "test.obj", &options, size, &|_| ()).unwrap();
+            assert_eq!(chunked, whole);
+            assert_eq!(report.warnings.iter().map(ToString::to_string).collect::<Vec<_>>(), whole_warnings);
+        }
+    }
+
+    #[test]
+    fn strict_mode_reports_the_first_error_of_the_file() {
+
+        // the bad position on line 3 is found by the first pass, before the face on line 2 is resolved
+        let source = "v 0 0 0\nf 1 1 4\nv x 0 0\n";
+        let options = LoadOptions { mode: ParseMode::Strict, ..LoadOptions::default() };
+        match parse_chunks(source.as_bytes(), "test.obj", &options, 1, &|_| ()) {
+            Err(ObjError::IndexOutOfRange { location, .. }) => assert_eq!(location.line, 2),
+            Err(other) => panic!("unexpected error {}", other),
+            Ok(_) => panic!("expected an out of range index")
+        }
+    }
+
+    #[test]
+    fn loads_bundled_model_without_a_device() {
+
+        let (data, report) = MeshData::from_obj_file(concat!(env!("CARGO_MANIFEST_DIR"), "/src/teapot.obj"), &LoadOptions::default()).unwrap();
+        assert!(report.warnings.is_empty());
+        assert_eq!(data.indices.len(), 1024 * 3);
+        assert_eq!(data.positions.len(), data.normals.len());
+        assert_eq!(data.submeshes.iter().map(|submesh| submesh.name.as_str()).collect::<Vec<_>>(), vec!["Object001"]);
+    }
+
+    #[test]
+    fn progress_ends_at_the_whole_file() {
+
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n".repeat(20);
+        let reported = std::sync::Mutex::new(Vec::new());
+        parse_chunks(source.as_bytes(), "test.obj", &LoadOptions::default(), 16, &|fraction| reported.lock().unwrap().push(fraction)).unwrap();
+        let reported = reported.into_inner().unwrap();
+        assert!(reported.len() > 2);
+        assert!(reported.iter().all(|&fraction| (0.0..=1.0).contains(&fraction)));
+        assert_eq!(reported.last(), Some(&1.0));
+    }
+}
@@ -0,0 +1,681 @@
+use std::collections::HashMap;
+use std::io;
+
+use super::bounds::Aabb;
+use super::error::{LoadReport, Location, ObjError};
+use super::mtl::MaterialData;
+use super::normals;
+use super::tokenizer::{Line, Lines};
+use super::triangulate::triangulate;
+use super::{LoadOptions, MaterialRange, MeshData, ScalarProperty, Submesh};
+
+const DEFAULT_SUBMESH: &str = "default";
+// smoothing group given to every face, PLY has no way to mark hard edges
+const SMOOTH: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64
+}
+
+impl Scalar {
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(Scalar::I8),
+            "uchar" | "uint8" => Some(Scalar::U8),
+            "short" | "int16" => Some(Scalar::I16),
+            "ushort" | "uint16" => Some(Scalar::U16),
+            "int" | "int32" => Some(Scalar::I32),
+            "uint" | "uint32" => Some(Scalar::U32),
+            "float" | "float32" => Some(Scalar::F32),
+            "double" | "float64" => Some(Scalar::F64),
+            _ => None
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8
+        }
+    }
+
+    // integer colour channels run up to the largest value of their type, float ones up to 1
+    fn full_scale(self) -> f64 {
+        match self {
+            Scalar::I8 => i8::MAX as f64,
+            Scalar::U8 => u8::MAX as f64,
+            Scalar::I16 => i16::MAX as f64,
+            Scalar::U16 => u16::MAX as f64,
+            Scalar::I32 => i32::MAX as f64,
+            Scalar::U32 => u32::MAX as f64,
+            Scalar::F32 | Scalar::F64 => 1.0
+        }
+    }
+
+    fn decode(self, bytes: &[u8], format: Format) -> f64 {
+
+        macro_rules! decode {
+            ($type:ty) => {{
+                let bytes = bytes.try_into().unwrap();
+                (if format == Format::BigEndian { <$type>::from_be_bytes(bytes) } else { <$type>::from_le_bytes(bytes) }) as f64
+            }};
+        }
+        match self {
+            Scalar::I8 => bytes[0] as i8 as f64,
+            Scalar::U8 => bytes[0] as f64,
+            Scalar::I16 => decode!(i16),
+            Scalar::U16 => decode!(u16),
+            Scalar::I32 => decode!(i32),
+            Scalar::U32 => decode!(u32),
+            Scalar::F32 => decode!(f32),
+            Scalar::F64 => decode!(f64)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar }
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    kind: Kind
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>
+}
+
+impl Element {
+
+    fn find(&self, names: &[&str]) -> Option<usize> {
+        self.properties.iter().position(|property| names.contains(&property.name.as_str()))
+    }
+
+    fn scalar_type(&self, property: usize) -> Scalar {
+        match self.properties[property].kind {
+            Kind::Scalar(scalar) => scalar,
+            Kind::List { item, .. } => item
+        }
+    }
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+    // byte offset of the data, and the number of lines before it
+    data_start: usize,
+    lines: usize
+}
+
+fn parse_header(bytes: &[u8], filename: &str) -> Result<Header, ObjError> {
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut offset = 0;
+    let mut number = 0;
+    loop {
+        let end = bytes[offset..].iter().position(|&byte| byte == b'\n')
+            .ok_or_else(|| ObjError::invalid_data(filename, "the header has no end_header".to_string()))?;
+        let text = std::str::from_utf8(&bytes[offset..offset + end])
+            .map_err(|err| ObjError::invalid_data(filename, err.to_string()))?;
+        let text = text.trim_end_matches('\r');
+        offset += end + 1;
+        number += 1;
+
+        let line = Line { file: filename, number, text };
+        let tokens = text.split_whitespace().collect::<Vec<&str>>();
+        let unsupported = || ObjError::UnsupportedDirective(line.locate(text.trim()));
+        match tokens.as_slice() {
+            ["ply"] if number == 1 => (),
+            _ if number == 1 => return Err(ObjError::invalid_data(filename, "not a PLY file".to_string())),
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::LittleEndian,
+                    "binary_big_endian" => Format::BigEndian,
+                    _ => return Err(unsupported())
+                });
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => (),
+            ["element", name, count] => {
+                let count = count.parse().map_err(|_| ObjError::BadNumber(line.locate(count)))?;
+                elements.push(Element { name: name.to_string(), count, properties: Vec::new() });
+            }
+            ["property", "list", count, item, name] => {
+                let element = elements.last_mut().ok_or_else(unsupported)?;
+                let count = Scalar::from_name(count).ok_or_else(unsupported)?;
+                let item = Scalar::from_name(item).ok_or_else(unsupported)?;
+                element.properties.push(Property { name: name.to_string(), kind: Kind::List { count, item } });
+            }
+            ["property", scalar, name] => {
+                let element = elements.last_mut().ok_or_else(unsupported)?;
+                let scalar = Scalar::from_name(scalar).ok_or_else(unsupported)?;
+                element.properties.push(Property { name: name.to_string(), kind: Kind::Scalar(scalar) });
+            }
+            ["end_header"] => break,
+            _ => return Err(unsupported())
+        }
+    }
+    let format = format.ok_or_else(|| ObjError::invalid_data(filename, "the header has no format".to_string()))?;
+    Ok(Header { format, elements, data_start: offset, lines: number })
+}
+
+// the values of one element, scalars and lists indexed by property. `location` is the line it
+// was read from in ASCII files
+#[derive(Default)]
+struct Record {
+    scalars: Vec<f64>,
+    lists: Vec<Vec<f64>>,
+    location: Option<Location>
+}
+
+impl Record {
+
+    fn reset(&mut self, element: &Element) {
+        self.scalars.clear();
+        self.scalars.resize(element.properties.len(), 0.0);
+        self.lists.resize_with(element.properties.len(), Vec::new);
+        self.lists.iter_mut().for_each(Vec::clear);
+        self.location = None;
+    }
+}
+
+trait Records {
+    fn read(&mut self, element: &Element, record: &mut Record) -> Result<(), ObjError>;
+    // nothing is left to read, so a failed record can't be skipped
+    fn at_end(&self) -> bool;
+}
+
+struct AsciiRecords<'a> {
+    filename: &'a str,
+    lines: Lines<'a>,
+    done: bool
+}
+
+impl Records for AsciiRecords<'_> {
+
+    // every element is on a line of its own
+    fn read(&mut self, element: &Element, record: &mut Record) -> Result<(), ObjError> {
+
+        record.reset(element);
+        let (number, text) = loop {
+            match self.lines.next() {
+                Some((_, text)) if text.trim().is_empty() => continue,
+                Some(line) => break line,
+                None => {
+                    self.done = true;
+                    return Err(ObjError::invalid_data(self.filename, format!("the data ends before the last {}", element.name)));
+                }
+            }
+        };
+        let line = Line { file: self.filename, number, text: &text };
+        let mut tokens = text.split_whitespace();
+        let mut next = || {
+            let token = tokens.next().ok_or_else(|| ObjError::WrongComponentCount { location: line.locate(text.trim()), expected: "more" })?;
+            token.parse::<f64>().map_err(|_| ObjError::BadNumber(line.locate(token)))
+        };
+        for (i, property) in element.properties.iter().enumerate() {
+            match property.kind {
+                Kind::Scalar(_) => record.scalars[i] = next()?,
+                Kind::List { .. } => {
+                    let count = next()? as usize;
+                    for _ in 0..count {
+                        let value = next()?;
+                        record.lists[i].push(value);
+                    }
+                }
+            }
+        }
+        if tokens.next().is_some() {
+            return Err(ObjError::WrongComponentCount { location: line.locate(text.trim()), expected: "fewer" });
+        }
+        record.location = Some(line.locate(text.trim()));
+        Ok(())
+    }
+
+    fn at_end(&self) -> bool {
+        self.done
+    }
+}
+
+struct BinaryRecords<'a> {
+    filename: &'a str,
+    bytes: &'a [u8],
+    offset: usize,
+    format: Format
+}
+
+impl BinaryRecords<'_> {
+
+    fn next(&mut self, scalar: Scalar) -> Result<f64, ObjError> {
+
+        let end = self.offset + scalar.size();
+        let Some(bytes) = self.bytes.get(self.offset..end) else {
+            // a partial value is the end of the data, nothing after it can be read
+            self.offset = self.bytes.len();
+            return Err(ObjError::invalid_data(self.filename, "the data ends early".to_string()));
+        };
+        self.offset = end;
+        Ok(scalar.decode(bytes, self.format))
+    }
+}
+
+impl Records for BinaryRecords<'_> {
+
+    fn read(&mut self, element: &Element, record: &mut Record) -> Result<(), ObjError> {
+
+        record.reset(element);
+        for (i, property) in element.properties.iter().enumerate() {
+            match property.kind {
+                Kind::Scalar(scalar) => record.scalars[i] = self.next(scalar)?,
+                Kind::List { count, item } => {
+                    let count = self.next(count)? as usize;
+                    for _ in 0..count {
+                        let value = self.next(item)?;
+                        record.lists[i].push(value);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn at_end(&self) -> bool {
+        self.offset >= self.bytes.len()
+    }
+}
+
+// where the known vertex properties are, the rest are kept as scalar properties
+struct VertexLayout {
+    position: [usize; 3],
+    normal: Option<[usize; 3]>,
+    color: Option<([usize; 3], f64)>,
+    texcoord: Option<[usize; 2]>,
+    scalars: Vec<usize>
+}
+
+impl VertexLayout {
+
+    fn new(element: &Element, filename: &str) -> Result<Self, ObjError> {
+
+        let find = |names: &[&[&str]]| names.iter().map(|names| element.find(names)).collect::<Option<Vec<usize>>>();
+        let position = find(&[&["x"], &["y"], &["z"]])
+            .ok_or_else(|| ObjError::invalid_data(filename, "vertices have no x, y and z".to_string()))?;
+        let normal = find(&[&["nx"], &["ny"], &["nz"]]);
+        let color = find(&[&["red", "diffuse_red", "r"], &["green", "diffuse_green", "g"], &["blue", "diffuse_blue", "b"]])
+            .map(|channels| (channels.clone(), element.scalar_type(channels[0]).full_scale()));
+        let texcoord = find(&[&["u", "s", "texture_u", "texture_s"], &["v", "t", "texture_v", "texture_t"]]);
+
+        let known = position.iter()
+            .chain(normal.iter().flatten())
+            .chain(color.iter().flat_map(|(channels, _)| channels))
+            .chain(texcoord.iter().flatten())
+            .copied()
+            .chain(element.find(&["alpha", "diffuse_alpha", "a"]))
+            .collect::<Vec<usize>>();
+        let scalars = (0..element.properties.len())
+            .filter(|i| !known.contains(i) && matches!(element.properties[*i].kind, Kind::Scalar(_)))
+            .collect();
+        let array = |indices: Vec<usize>| indices.try_into().unwrap();
+        Ok(Self {
+            position: array(position),
+            normal: normal.map(array),
+            color: color.map(|(channels, scale)| (array(channels), scale)),
+            texcoord: texcoord.map(|t| [t[0], t[1]]),
+            scalars
+        })
+    }
+}
+
+// everything read from the elements, before normals are generated and vertices split
+#[derive(Default)]
+struct Elements {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    colors: Vec<[f32; 3]>,
+    texcoords: Vec<[f32; 2]>,
+    scalars: Vec<ScalarProperty>,
+    triangles: Vec<[u32; 3]>,
+    segments: Vec<[u32; 2]>,
+    has_vertices: bool
+}
+
+impl Elements {
+
+    fn add_vertex(&mut self, layout: &VertexLayout, record: &Record) {
+
+        let value = |i: usize| record.scalars[i] as f32;
+        self.positions.push(layout.position.map(value));
+        if let Some(normal) = layout.normal {
+            self.normals.push(normal.map(value));
+        }
+        if let Some((channels, scale)) = layout.color {
+            self.colors.push(channels.map(|i| (record.scalars[i] / scale) as f32));
+        }
+        if let Some(texcoord) = layout.texcoord {
+            self.texcoords.push(texcoord.map(value));
+        }
+        for (scalar, &i) in self.scalars.iter_mut().zip(&layout.scalars) {
+            scalar.values.push(value(i));
+        }
+    }
+
+    // indices refer to the vertices, which come first in every file this reads
+    fn vertex_index(&self, value: f64, record: &Record, filename: &str) -> Result<u32, ObjError> {
+
+        let count = self.positions.len();
+        if value >= 0.0 && (value as usize) < count {
+            return Ok(value as u32);
+        }
+        Err(match &record.location {
+            Some(location) => ObjError::IndexOutOfRange { location: Location { text: value.to_string(), ..location.clone() }, count },
+            None => ObjError::invalid_data(filename, format!("index {} is out of range, only {} vertices", value, count))
+        })
+    }
+
+    fn add_face(&mut self, indices: &[f64], record: &Record, filename: &str) -> Result<(), ObjError> {
+
+        let polygon = indices.iter().map(|&value| self.vertex_index(value, record, filename)).collect::<Result<Vec<u32>, ObjError>>()?;
+        if polygon.len() < 3 {
+            return Err(match &record.location {
+                Some(location) => ObjError::TooFewVertices(location.clone()),
+                None => ObjError::invalid_data(filename, format!("a face has {} vertices", polygon.len()))
+            });
+        }
+        let corners = polygon.iter().map(|&i| self.positions[i as usize]).collect::<Vec<[f32; 3]>>();
+        for triangle in triangulate(&corners) {
+            self.triangles.push(triangle.map(|corner| polygon[corner]));
+        }
+        Ok(())
+    }
+}
+
+fn read_elements(bytes: &[u8], filename: &str, options: &LoadOptions, report: &mut LoadReport) -> Result<Elements, ObjError> {
+
+    let header = parse_header(bytes, filename)?;
+    let data = &bytes[header.data_start..];
+    let text;
+    let mut records: Box<dyn Records> = match header.format {
+        Format::Ascii => {
+            text = std::str::from_utf8(data).map_err(|err| ObjError::Io { file: filename.to_string(), source: io::Error::new(io::ErrorKind::InvalidData, err) })?;
+            Box::new(AsciiRecords { filename, lines: Lines::new(text, header.lines + 1), done: false })
+        }
+        format => Box::new(BinaryRecords { filename, bytes: data, offset: 0, format })
+    };
+
+    let mut elements = Elements::default();
+    let mut record = Record::default();
+    'elements: for element in &header.elements {
+        let layout = if element.name == "vertex" {
+            let layout = VertexLayout::new(element, filename)?;
+            elements.has_vertices = true;
+            // the count comes from the file, every record takes at least a byte so that bounds it
+            let capacity = element.count.min(data.len());
+            elements.scalars = layout.scalars.iter()
+                .map(|&i| ScalarProperty { name: element.properties[i].name.clone(), values: Vec::with_capacity(capacity) })
+                .collect();
+            Some(layout)
+        } else {
+            None
+        };
+        let face_indices = element.find(&["vertex_indices", "vertex_index"]);
+        let edge_ends = element.find(&["vertex1"]).zip(element.find(&["vertex2"]));
+
+        for _ in 0..element.count {
+            let result = records.read(element, &mut record).and_then(|_| {
+                match (element.name.as_str(), &layout) {
+                    ("vertex", Some(layout)) => elements.add_vertex(layout, &record),
+                    ("face", _) => if let Some(indices) = face_indices {
+                        elements.add_face(&record.lists[indices], &record, filename)?;
+                    },
+                    ("edge", _) => if let Some((first, second)) = edge_ends {
+                        let first = elements.vertex_index(record.scalars[first], &record, filename)?;
+                        let second = elements.vertex_index(record.scalars[second], &record, filename)?;
+                        elements.segments.push([first, second]);
+                    },
+                    // other elements are read past
+                    _ => ()
+                }
+                Ok(())
+            });
+            if let Err(err) = result {
+                options.mode.problem(err, report)?;
+                if records.at_end() {
+                    break 'elements;
+                }
+                // a vertex that could not be read is kept at the origin, so later indices still match
+                if let Some(layout) = &layout {
+                    record.reset(element);
+                    elements.add_vertex(layout, &record);
+                }
+            }
+        }
+    }
+    if !elements.has_vertices {
+        return Err(ObjError::invalid_data(filename, "the file has no vertex element".to_string()));
+    }
+    Ok(elements)
+}
+
+// reads ASCII and binary PLY. faces go through the same triangulation as OBJ faces, and get
+// generated normals when the vertices have none. a file with neither faces nor edges is a point
+// set, every vertex is drawn as a point. vertex properties other than position, normal, colour
+// and texture coordinates are kept as scalar properties
+pub fn parse(bytes: &[u8], filename: &str, options: &LoadOptions) -> Result<(MeshData, LoadReport), ObjError> {
+
+    let mut report = LoadReport::default();
+    let elements = read_elements(bytes, filename, options, &mut report)?;
+
+    // vertices are split where the generated normals of their corners differ, `sources` is the
+    // vertex of the file each one comes from
+    let mut sources: Vec<usize> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+    let mut indices: Vec<u32> = Vec::with_capacity(elements.triangles.len() * 3);
+    if elements.normals.is_empty() && !elements.triangles.is_empty() {
+        let groups = vec![SMOOTH; elements.triangles.len()];
+        let generated = normals::generate(&elements.positions, &elements.triangles, &groups, options.crease_angle.into());
+        let mut unique: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+        for (&vertex, normal) in elements.triangles.iter().flatten().zip(generated) {
+            indices.push(*unique.entry((vertex, normal.map(f32::to_bits))).or_insert_with(|| {
+                sources.push(vertex as usize);
+                normals.push(normal);
+                (sources.len() - 1) as u32
+            }));
+        }
+        // lines and points use the first copy of their vertex, they are drawn unlit anyway
+        let mut first_copy = HashMap::new();
+        for (copy, &source) in sources.iter().enumerate() {
+            first_copy.entry(source).or_insert(copy as u32);
+        }
+        for vertex in elements.segments.iter().flatten() {
+            first_copy.entry(*vertex as usize).or_insert_with(|| {
+                sources.push(*vertex as usize);
+                normals.push([0.0; 3]);
+                (sources.len() - 1) as u32
+            });
+        }
+        return Ok((build(&elements, sources, normals, indices, |vertex| first_copy[&(vertex as usize)]), report));
+    }
+
+    sources = (0..elements.positions.len()).collect();
+    normals = if elements.normals.is_empty() { vec![[0.0; 3]; sources.len()] } else { elements.normals.clone() };
+    indices.extend(elements.triangles.iter().flatten());
+    Ok((build(&elements, sources, normals, indices, |vertex| vertex), report))
+}
+
+fn build(elements: &Elements, sources: Vec<usize>, normals: Vec<[f32; 3]>, indices: Vec<u32>, vertex_of: impl Fn(u32) -> u32) -> MeshData {
+
+    let copy = |values: &[[f32; 3]]| sources.iter().map(|&source| values[source]).collect::<Vec<[f32; 3]>>();
+    let mut mesh = MeshData {
+        positions: copy(&elements.positions),
+        normals,
+        colors: if elements.colors.is_empty() { Vec::new() } else { copy(&elements.colors) },
+        texcoords: sources.iter().filter_map(|&source| elements.texcoords.get(source).copied()).collect(),
+        scalars: elements.scalars.iter().map(|scalar| ScalarProperty {
+            name: scalar.name.clone(),
+            values: sources.iter().map(|&source| scalar.values[source]).collect()
+        }).collect(),
+        indices,
+        line_indices: elements.segments.iter().flatten().map(|&vertex| vertex_of(vertex)).collect(),
+        ..MeshData::default()
+    };
+    if mesh.indices.is_empty() && mesh.line_indices.is_empty() {
+        mesh.point_indices = (0..mesh.positions.len() as u32).collect();
+    }
+    if !mesh.indices.is_empty() {
+        let bounds = Aabb::from_points(mesh.indices.iter().map(|&i| &mesh.positions[i as usize]));
+        let indices = 0..mesh.indices.len() as u32;
+        mesh.submeshes.push(Submesh {
+            name: DEFAULT_SUBMESH.to_string(),
+            indices: indices.clone(),
+            material_ranges: vec![MaterialRange { material: 0, indices }],
+            bounds
+        });
+    }
+    mesh.materials.push(MaterialData::default());
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ParseMode;
+
+    const HEADER: &str = "element vertex 5\n\
+        property float x\nproperty float y\nproperty float z\n\
+        property uchar red\nproperty uchar green\nproperty uchar blue\n\
+        property float confidence\n\
+        element face 2\nproperty list uchar int vertex_indices\n\
+        end_header\n";
+    const POSITIONS: [[f32; 3]; 5] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0], [2.0, 0.0, 0.0]];
+    const FACES: [&[i32]; 2] = [&[0, 1, 2, 3], &[1, 4, 2]];
+
+    fn ascii() -> String {
+
+        let mut source = format!("ply\nformat ascii 1.0\ncomment made by hand\n{}", HEADER);
+        for (i, [x, y, z]) in POSITIONS.iter().enumerate() {
+            source += &format!("{} {} {} 255 0 {} {}\n", x, y, z, i * 50, i as f32 * 0.25);
+        }
+        for face in FACES {
+            source += &format!("{} {}\n", face.len(), face.iter().map(i32::to_string).collect::<Vec<_>>().join(" "));
+        }
+        source
+    }
+
+    fn binary(big_endian: bool) -> Vec<u8> {
+
+        let name = if big_endian { "binary_big_endian" } else { "binary_little_endian" };
+        let mut bytes = format!("ply\nformat {} 1.0\n{}", name, HEADER).into_bytes();
+        let float = |bytes: &mut Vec<u8>, value: f32| bytes.extend_from_slice(&if big_endian { value.to_be_bytes() } else { value.to_le_bytes() });
+        for (i, position) in POSITIONS.iter().enumerate() {
+            for &value in position {
+                float(&mut bytes, value);
+            }
+            bytes.extend_from_slice(&[255, 0, i as u8 * 50]);
+            float(&mut bytes, i as f32 * 0.25);
+        }
+        for face in FACES {
+            bytes.push(face.len() as u8);
+            for &index in face {
+                bytes.extend_from_slice(&if big_endian { index.to_be_bytes() } else { index.to_le_bytes() });
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn ascii_faces_colours_and_scalars() {
+
+        let (mesh, report) = MeshData::from_ply(ascii().as_bytes(), "test.ply", &LoadOptions::default()).unwrap();
+        assert!(report.warnings.is_empty());
+        // the quad is split in two, and all faces lie flat so no vertex needs a second normal
+        assert_eq!(mesh.indices.len(), 9);
+        assert_eq!(mesh.positions.len(), 5);
+        assert!(mesh.normals.iter().all(|&normal| normal == [0.0, 0.0, 1.0]));
+        assert_eq!(mesh.colors[4], [1.0, 0.0, 200.0 / 255.0]);
+        assert_eq!(mesh.get_scalar("confidence").unwrap().values, [0.0, 0.25, 0.5, 0.75, 1.0]);
+    }
+
+    #[test]
+    fn both_binary_byte_orders_match_ascii() {
+
+        let options = LoadOptions::default();
+        let (ascii, _) = MeshData::from_ply(ascii().as_bytes(), "test.ply", &options).unwrap();
+        for big_endian in [false, true] {
+            let (binary, report) = MeshData::from_ply(&binary(big_endian)[..], "test.ply", &options).unwrap();
+            assert!(report.warnings.is_empty());
+            assert_eq!(binary, ascii);
+        }
+    }
+
+    #[test]
+    fn vertices_alone_are_a_point_set() {
+
+        let source = "ply\nformat ascii 1.0\nelement vertex 3\nproperty double x\nproperty double y\nproperty double z\n\
+            property float curvature\nend_header\n0 0 0 0.5\n1 0 0 -1\n0 1 0 2\n";
+        let (mut mesh, _) = MeshData::from_ply(source.as_bytes(), "scan.ply", &LoadOptions::default()).unwrap();
+        assert_eq!((mesh.indices.len(), mesh.point_indices.len()), (0, 3));
+        assert!(mesh.submeshes.is_empty());
+
+        assert!(mesh.color_by_scalar("curvature"));
+        assert_eq!(mesh.colors[1], [0.0, 0.0, 1.0]);
+        assert_eq!(mesh.colors[2], [1.0, 0.0, 0.0]);
+        assert!(!mesh.color_by_scalar("confidence"));
+    }
+
+    #[test]
+    fn out_of_range_index_is_located() {
+
+        let source = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+            element face 2\nproperty list uchar uint vertex_index\nend_header\n\
+            0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n3 0 1 7\n";
+        let strict = LoadOptions { mode: ParseMode::Strict, ..LoadOptions::default() };
+        match MeshData::from_ply(source.as_bytes(), "test.ply", &strict) {
+            Err(ObjError::IndexOutOfRange { location, count }) => assert_eq!((location.line, location.text.as_str(), count), (14, "7", 3)),
+            Err(other) => panic!("unexpected error {}", other),
+            Ok(_) => panic!("expected an error")
+        }
+        let (mesh, report) = MeshData::from_ply(source.as_bytes(), "test.ply", &LoadOptions::default()).unwrap();
+        assert_eq!((mesh.indices.len(), report.warnings.len()), (3, 1));
+    }
+
+    #[test]
+    fn huge_element_counts_are_only_believed_as_far_as_the_data_goes() {
+
+        let header = HEADER.replace("element vertex 5", "element vertex 1000000000000000");
+        let mut bytes = format!("ply\nformat binary_little_endian 1.0\n{}", header).into_bytes();
+        bytes.extend_from_slice(&[0; 20]);
+        let strict = LoadOptions { mode: ParseMode::Strict, ..LoadOptions::default() };
+        assert!(parse(&bytes, "huge.ply", &strict).is_err());
+
+        // the one whole vertex is kept, the rest is a single warning
+        let (mesh, report) = parse(&bytes, "huge.ply", &LoadOptions::default()).unwrap();
+        assert_eq!(mesh.positions.len(), 1);
+        assert_eq!(report.warnings.len(), 1);
+    }
+}
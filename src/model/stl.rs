@@ -0,0 +1,336 @@
+use super::bounds::Aabb;
+use super::error::{LoadReport, Location, ObjError};
+use super::mtl::MaterialData;
+use super::tokenizer::{parse_floats, Line, Lines};
+use super::{LoadOptions, MaterialRange, MeshData, Submesh};
+
+const HEADER_SIZE: usize = 80;
+// normal, three corners and the attribute word
+const TRIANGLE_SIZE: usize = 50;
+const DEFAULT_SOLID: &str = "default";
+
+// a triangle as written in the file, corners in counter-clockwise order
+struct Facet {
+    normal: [f32; 3],
+    corners: [[f32; 3]; 3],
+    color: Option<[f32; 3]>
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+impl Facet {
+
+    // many writers leave the normal at zero, it is then found from the winding of the corners
+    fn unit_normal(&self) -> [f32; 3] {
+
+        let length = |[x, y, z]: [f32; 3]| (x * x + y * y + z * z).sqrt();
+        let normalized = |v: [f32; 3]| {
+            let length = length(v);
+            (length.is_finite() && length > 0.0).then(|| v.map(|value| value / length))
+        };
+        let [a, b, c] = self.corners;
+        let (ab, ac) = (sub(b, a), sub(c, a));
+        let winding = [ab[1] * ac[2] - ab[2] * ac[1], ab[2] * ac[0] - ab[0] * ac[2], ab[0] * ac[1] - ab[1] * ac[0]];
+        normalized(self.normal).or_else(|| normalized(winding)).unwrap_or([0.0, 0.0, 1.0])
+    }
+}
+
+// the facets of one `solid`, binary files have a single one
+struct Solid {
+    name: String,
+    facets: Vec<Facet>
+}
+
+// whether the triangle count in the header accounts for the size of the file exactly
+fn has_binary_size(bytes: &[u8]) -> bool {
+
+    if bytes.len() < HEADER_SIZE + 4 {
+        return false;
+    }
+    let count = u32::from_le_bytes(bytes[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap()) as usize;
+    count.checked_mul(TRIANGLE_SIZE).and_then(|size| size.checked_add(HEADER_SIZE + 4)) == Some(bytes.len())
+}
+
+fn starts_with_solid(bytes: &[u8]) -> bool {
+    let start = bytes.iter().position(|byte| !byte.is_ascii_whitespace()).unwrap_or(bytes.len());
+    bytes[start..].starts_with(b"solid")
+}
+
+// binary files may start with "solid" too, but then their size gives them away
+fn is_binary(bytes: &[u8]) -> bool {
+    has_binary_size(bytes) || !starts_with_solid(bytes)
+}
+
+// whether a file looks like STL at all, which binary files only show through their size
+pub fn sniff(bytes: &[u8]) -> bool {
+    has_binary_size(bytes) || starts_with_solid(bytes)
+}
+
+// the value after `key` in the header, Materialise Magics writes its colours there
+fn header_value<'a>(header: &'a [u8], key: &[u8], len: usize) -> Option<&'a [u8]> {
+    let start = header.windows(key.len()).position(|window| window == key)? + key.len();
+    header.get(start..start + len)
+}
+
+fn rgb(rgba: &[u8]) -> [f32; 3] {
+    [rgba[0], rgba[1], rgba[2]].map(|value| value as f32 / 255.0)
+}
+
+// the attribute word of a facet holds a 5 bit per channel colour in two conventions. VisCAM and
+// SolidView put blue in the low bits and set bit 15 for a valid colour. Magics, recognised by
+// `COLOR=` in the header, puts red in the low bits and sets bit 15 to use the header colour
+fn facet_color(attribute: u16, magics: Option<[f32; 3]>) -> Option<[f32; 3]> {
+
+    let channel = |shift: u16| ((attribute >> shift) & 0x1f) as f32 / 31.0;
+    match magics {
+        Some(default) if attribute & 0x8000 != 0 => Some(default),
+        Some(_) => Some([channel(0), channel(5), channel(10)]),
+        None if attribute & 0x8000 != 0 => Some([channel(10), channel(5), channel(0)]),
+        None => None
+    }
+}
+
+fn parse_binary(bytes: &[u8], filename: &str, options: &LoadOptions, report: &mut LoadReport, material: &mut MaterialData) -> Result<Solid, ObjError> {
+
+    if bytes.len() < HEADER_SIZE + 4 {
+        return Err(ObjError::invalid_data(filename, format!("{} bytes is too short for a binary STL header", bytes.len())));
+    }
+    let header = &bytes[..HEADER_SIZE];
+    let declared = u32::from_le_bytes(bytes[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap()) as usize;
+    let available = (bytes.len() - HEADER_SIZE - 4) / TRIANGLE_SIZE;
+    if declared != available || !(bytes.len() - HEADER_SIZE - 4).is_multiple_of(TRIANGLE_SIZE) {
+        let mismatch = ObjError::invalid_data(filename, format!("header declares {} triangles, the file holds {}", declared, available));
+        options.mode.problem(mismatch, report)?;
+    }
+
+    let magics = header_value(header, b"COLOR=", 4).map(rgb);
+    if let Some(colors) = header_value(header, b"MATERIAL=", 12) {
+        material.diffuse = rgb(&colors[0..4]);
+        material.specular = rgb(&colors[4..8]);
+        material.ambient = rgb(&colors[8..12]);
+    }
+
+    let float = |bytes: &[u8], i: usize| f32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap());
+    let facets = bytes[HEADER_SIZE + 4..].chunks_exact(TRIANGLE_SIZE).take(declared).map(|triangle| {
+        let vector = |first: usize| [float(triangle, first), float(triangle, first + 1), float(triangle, first + 2)];
+        Facet {
+            normal: vector(0),
+            corners: [vector(3), vector(6), vector(9)],
+            color: facet_color(u16::from_le_bytes([triangle[48], triangle[49]]), magics)
+        }
+    }).collect();
+
+    // the header is free text, which some writers fill with the name of the part
+    let name = String::from_utf8_lossy(header).trim_matches(|c: char| c == '\0' || c.is_whitespace()).to_string();
+    let name = match name.strip_prefix("solid") {
+        Some(rest) if !rest.trim().is_empty() => rest.trim().to_string(),
+        _ => DEFAULT_SOLID.to_string()
+    };
+    Ok(Solid { name, facets })
+}
+
+// reads an ASCII file line by line, collecting the corners between `facet` and `endfacet`
+#[derive(Default)]
+struct AsciiParser {
+    solids: Vec<Solid>,
+    facet: Option<([f32; 3], Vec<[f32; 3]>, Location)>
+}
+
+impl AsciiParser {
+
+    fn solid(&mut self) -> &mut Solid {
+        if self.solids.is_empty() {
+            self.solids.push(Solid { name: DEFAULT_SOLID.to_string(), facets: Vec::new() });
+        }
+        self.solids.last_mut().unwrap()
+    }
+
+    fn apply(&mut self, line: &Line) -> Result<(), ObjError> {
+
+        let (keyword, rest) = match line.split_keyword() {
+            Some(split) => split,
+            None => return Ok(())
+        };
+        match keyword {
+            "solid" => {
+                let name = if rest.is_empty() { DEFAULT_SOLID } else { rest };
+                self.solids.push(Solid { name: name.to_string(), facets: Vec::new() });
+            }
+            "facet" => {
+                let values = rest.strip_prefix("normal").ok_or_else(|| ObjError::UnsupportedDirective(line.locate(rest)))?;
+                let normal = parse_floats::<3>(line, values.trim(), 3, "3")?;
+                self.facet = Some((normal, Vec::new(), line.locate(line.text.trim())));
+            }
+            "vertex" => {
+                let corner = parse_floats::<3>(line, rest, 3, "3")?;
+                match &mut self.facet {
+                    Some((_, corners, _)) => corners.push(corner),
+                    None => return Err(ObjError::UnsupportedDirective(line.locate(keyword)))
+                }
+            }
+            "endfacet" => {
+                let (normal, corners, location) = self.facet.take().ok_or_else(|| ObjError::UnsupportedDirective(line.locate(keyword)))?;
+                if corners.len() < 3 {
+                    return Err(ObjError::TooFewVertices(location));
+                }
+                // a few writers put polygons in a facet, they are split into a fan
+                for i in 1..corners.len() - 1 {
+                    self.solid().facets.push(Facet { normal, corners: [corners[0], corners[i], corners[i + 1]], color: None });
+                }
+            }
+            "outer" | "endloop" | "endsolid" => (),
+            _ => return Err(ObjError::UnsupportedDirective(line.locate(keyword)))
+        }
+        Ok(())
+    }
+}
+
+fn parse_ascii(bytes: &[u8], filename: &str, options: &LoadOptions, report: &mut LoadReport) -> Result<Vec<Solid>, ObjError> {
+
+    let text = std::str::from_utf8(bytes).map_err(|err| ObjError::invalid_data(filename, err.to_string()))?;
+    let mut parser = AsciiParser::default();
+    for (number, text) in Lines::new(text, 1) {
+        let line = Line { file: filename, number, text: &text };
+        if let Err(err) = parser.apply(&line) {
+            options.mode.problem(err, report)?;
+            // a broken facet is dropped whole
+            parser.facet = None;
+        }
+    }
+    Ok(parser.solids)
+}
+
+// reads ASCII or binary STL. every facet gets its own three vertices with the facet normal,
+// welding then joins the corners of neighbouring facets that lie in the same plane. each ASCII
+// `solid` becomes a submesh
+pub fn parse(bytes: &[u8], filename: &str, options: &LoadOptions) -> Result<(MeshData, LoadReport), ObjError> {
+
+    let mut report = LoadReport::default();
+    let mut material = MaterialData::default();
+    let solids = if is_binary(bytes) {
+        vec![parse_binary(bytes, filename, options, &mut report, &mut material)?]
+    } else {
+        parse_ascii(bytes, filename, options, &mut report)?
+    };
+
+    let mut mesh = MeshData::default();
+    let colored = solids.iter().flat_map(|solid| &solid.facets).any(|facet| facet.color.is_some());
+    for solid in solids.into_iter().filter(|solid| !solid.facets.is_empty()) {
+        let start = mesh.indices.len() as u32;
+        let mut bounds = Aabb::empty();
+        for facet in &solid.facets {
+            let normal = facet.unit_normal();
+            for corner in facet.corners {
+                mesh.indices.push(mesh.positions.len() as u32);
+                mesh.positions.push(corner);
+                mesh.normals.push(normal);
+                if colored {
+                    // facets without a colour of their own show the material
+                    mesh.colors.push(facet.color.unwrap_or(material.diffuse));
+                }
+                bounds.grow(corner);
+            }
+        }
+        let indices = start..mesh.indices.len() as u32;
+        mesh.submeshes.push(Submesh {
+            name: solid.name,
+            indices: indices.clone(),
+            material_ranges: vec![MaterialRange { material: 0, indices }],
+            bounds
+        });
+    }
+    mesh.materials.push(material);
+    Ok((mesh, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ParseMode;
+
+    // normal, corners and attribute word
+    type RawFacet = ([f32; 3], [[f32; 3]; 3], u16);
+
+    fn binary(header: &[u8], facets: &[RawFacet]) -> Vec<u8> {
+
+        let mut bytes = vec![0; HEADER_SIZE];
+        bytes[..header.len()].copy_from_slice(header);
+        bytes.extend_from_slice(&(facets.len() as u32).to_le_bytes());
+        for (normal, corners, attribute) in facets {
+            for value in normal.iter().chain(corners.iter().flatten()) {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            bytes.extend_from_slice(&attribute.to_le_bytes());
+        }
+        bytes
+    }
+
+    const QUAD: [[[f32; 3]; 3]; 2] = [
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]],
+        [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]]
+    ];
+
+    #[test]
+    fn ascii_solids_become_submeshes() {
+
+        let source = "solid first\n\
+            facet normal 0 0 1\n outer loop\n  vertex 0 0 0\n  vertex 1 0 0\n  vertex 1 1 0\n endloop\nendfacet\n\
+            facet normal 0 0 0\n outer loop\n  vertex 0 0 0\n  vertex 1 1 0\n  vertex 0 1 0\n endloop\nendfacet\n\
+            endsolid first\n\
+            solid second\n\
+            facet normal 0 0 -1\n outer loop\n  vertex 0 0 1\n  vertex 0 1 1\n  vertex 1 0 1\n endloop\nendfacet\n\
+            endsolid second\n";
+        let (mesh, report) = MeshData::from_stl(source.as_bytes(), "test.stl", &LoadOptions::default()).unwrap();
+        assert!(report.warnings.is_empty());
+        assert_eq!(mesh.submeshes.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), ["first", "second"]);
+        // the two facets of the first solid share an edge in one plane, so welding joins two corners
+        assert_eq!(mesh.positions.len(), 7);
+        // the zero normal was found from the winding
+        assert!(mesh.normals.iter().take(4).all(|&normal| normal == [0.0, 0.0, 1.0]));
+        assert!(mesh.colors.is_empty());
+    }
+
+    #[test]
+    fn binary_starting_with_solid_is_recognised_by_its_size() {
+
+        let bytes = binary(b"solid exported by a binary writer", &[([0.0, 0.0, 1.0], QUAD[0], 0), ([0.0, 0.0, 1.0], QUAD[1], 0)]);
+        assert!(is_binary(&bytes));
+        let (mesh, _) = MeshData::from_stl(&bytes[..], "test.stl", &LoadOptions::default()).unwrap();
+        assert_eq!(mesh.indices.len(), 6);
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.submeshes[0].name, "exported by a binary writer");
+    }
+
+    #[test]
+    fn facet_colours_in_both_conventions() {
+
+        // VisCAM: pure red, blue in the low bits
+        let viscam = binary(b"", &[([0.0; 3], QUAD[0], 0x8000 | 0x1f << 10), ([0.0; 3], QUAD[1], 0)]);
+        let (mesh, _) = MeshData::from_stl(&viscam[..], "test.stl", &LoadOptions::default()).unwrap();
+        let colors = mesh.indices.iter().map(|&i| mesh.colors[i as usize]).collect::<Vec<_>>();
+        assert_eq!(colors[0], [1.0, 0.0, 0.0]);
+        assert_eq!(colors[3], MaterialData::default().diffuse);
+
+        // Magics: pure red in the low bits, and the green header colour for the other facet
+        let mut header = b"COLOR=".to_vec();
+        header.extend_from_slice(&[0, 255, 0, 255]);
+        let magics = binary(&header, &[([0.0; 3], QUAD[0], 0x1f), ([0.0; 3], QUAD[1], 0x8000)]);
+        let (mesh, _) = MeshData::from_stl(&magics[..], "test.stl", &LoadOptions::default()).unwrap();
+        let colors = mesh.indices.iter().map(|&i| mesh.colors[i as usize]).collect::<Vec<_>>();
+        assert_eq!((colors[0], colors[3]), ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]));
+    }
+
+    #[test]
+    fn truncated_binary_is_an_error_in_strict_mode() {
+
+        let mut bytes = binary(b"", &[([0.0, 0.0, 1.0], QUAD[0], 0), ([0.0, 0.0, 1.0], QUAD[1], 0)]);
+        bytes.truncate(bytes.len() - 10);
+        let strict = LoadOptions { mode: ParseMode::Strict, ..LoadOptions::default() };
+        assert!(MeshData::from_stl(&bytes[..], "test.stl", &strict).is_err());
+
+        let (mesh, report) = MeshData::from_stl(&bytes[..], "test.stl", &LoadOptions::default()).unwrap();
+        assert_eq!((mesh.indices.len(), report.warnings.len()), (3, 1));
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use super::MeshData;
+
+// tangent given to vertices no triangle with texture coordinates uses
+const DEFAULT_TANGENT: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+// the triangles of a mesh as mikktspace sees them, collecting a tangent for every corner
+struct Corners<'a> {
+    mesh: &'a MeshData,
+    tangents: Vec<[f32; 4]>
+}
+
+impl Corners<'_> {
+
+    fn vertex(&self, face: usize, corner: usize) -> usize {
+        self.mesh.indices[face * 3 + corner] as usize
+    }
+}
+
+impl mikktspace::Geometry for Corners<'_> {
+
+    fn num_faces(&self) -> usize {
+        self.mesh.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.mesh.positions[self.vertex(face, vert)]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.mesh.normals[self.vertex(face, vert)]
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.mesh.texcoords[self.vertex(face, vert)]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = tangent;
+    }
+}
+
+// generates MikkTSpace tangents for meshes with texture coordinates, leaving `tangents` empty
+// for the others. w is the sign of the bitangent, which is cross(normal, tangent) * w. corners
+// of one vertex that get different tangents, as on mirrored texture seams, are given their own
+// copy of the vertex
+pub fn generate(mesh: &mut MeshData) {
+
+    mesh.tangents.clear();
+    if mesh.texcoords.is_empty() || mesh.indices.is_empty() {
+        return;
+    }
+    let mut corners = Corners { mesh, tangents: vec![DEFAULT_TANGENT; mesh.indices.len()] };
+    if !mikktspace::generate_tangents(&mut corners) {
+        return;
+    }
+    let corner_tangents = corners.tangents;
+
+    let mut tangents : Vec<Option<[f32; 4]>> = vec![None; mesh.positions.len()];
+    let mut copies : HashMap<(u32, [u32; 4]), u32> = HashMap::new();
+    for (corner, tangent) in corner_tangents.into_iter().enumerate() {
+        let vertex = mesh.indices[corner];
+        match tangents[vertex as usize] {
+            None => tangents[vertex as usize] = Some(tangent),
+            Some(existing) if existing == tangent => (),
+            Some(_) => {
+                mesh.indices[corner] = *copies.entry((vertex, tangent.map(f32::to_bits))).or_insert_with(|| {
+                    let vertex = vertex as usize;
+                    mesh.positions.push(mesh.positions[vertex]);
+                    mesh.normals.push(mesh.normals[vertex]);
+                    mesh.texcoords.push(mesh.texcoords[vertex]);
+                    if !mesh.colors.is_empty() {
+                        mesh.colors.push(mesh.colors[vertex]);
+                    }
+                    for scalar in &mut mesh.scalars {
+                        scalar.values.push(scalar.values[vertex]);
+                    }
+                    tangents.push(Some(tangent));
+                    (mesh.positions.len() - 1) as u32
+                });
+            }
+        }
+    }
+    mesh.tangents = tangents.into_iter().map(|tangent| tangent.unwrap_or(DEFAULT_TANGENT)).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{LoadOptions, MeshData};
+
+    #[test]
+    fn quad_tangents_follow_u() {
+
+        let source = "v 0 0 0\nv 2 0 0\nv 2 0 -2\nv 0 0 -2\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 1 0\nf 1/1/1 2/2/1 3/3/1 4/4/1\n";
+        let (mesh, _) = MeshData::from_obj(source.as_bytes(), "quad.obj", &LoadOptions::default()).unwrap();
+        assert_eq!(mesh.tangents.len(), mesh.positions.len());
+        for tangent in &mesh.tangents {
+            let [x, y, z, w] = *tangent;
+            assert!((x - 1.0).abs() < 1e-5 && y.abs() < 1e-5 && z.abs() < 1e-5, "{:?}", tangent);
+            // v runs along -z, which is cross(normal, tangent) for a +y normal and +x tangent
+            assert_eq!(w, 1.0);
+        }
+    }
+
+    #[test]
+    fn mirrored_texture_splits_shared_vertices() {
+
+        // both triangles share an edge, the second one has its texture mirrored in u
+        let source = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 2 0 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/2/1 3/3/1\nf 2/2/1 4/4/1 3/3/1\n";
+        let (mesh, _) = MeshData::from_obj(source.as_bytes(), "mirror.obj", &LoadOptions::default()).unwrap();
+        assert_eq!(mesh.positions.len(), 6);
+        let tangent_x = |corner: usize| mesh.tangents[mesh.indices[corner] as usize][0];
+        assert!(tangent_x(0) > 0.0 && tangent_x(3) < 0.0);
+    }
+}
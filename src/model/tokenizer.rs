@@ -0,0 +1,310 @@
+use std::{borrow::Cow, io::{self, BufRead}};
+
+use super::error::{Location, ObjError};
+
+// a logical line of an OBJ or MTL file, with continuations joined and comments removed
+pub struct Line<'a> {
+    pub file: &'a str,
+    // number of the first physical line it was read from
+    pub number: usize,
+    pub text: &'a str
+}
+
+impl Line<'_> {
+
+    // every token is a slice of `text`, so its offset in there gives the column
+    pub fn locate(&self, token: &str) -> Location {
+        Location {
+            file: self.file.to_string(),
+            line: self.number,
+            column: token.as_ptr() as usize - self.text.as_ptr() as usize + 1,
+            text: token.to_string()
+        }
+    }
+
+    // the first token and everything after it, None for lines with nothing on them
+    pub fn split_keyword(&self) -> Option<(&str, &str)> {
+
+        let text = self.text.trim_start();
+        let end = text.find(char::is_whitespace).unwrap_or(text.len());
+        let (keyword, rest) = text.split_at(end);
+        if keyword.is_empty() {
+            None
+        } else {
+            Some((keyword, rest.trim()))
+        }
+    }
+}
+
+// `#` starts a comment when it begins a token
+fn strip_comment(text: &str) -> &str {
+
+    let comment = text.char_indices()
+        .find(|&(i, c)| c == '#' && text[..i].chars().next_back().is_none_or(char::is_whitespace))
+        .map(|(i, _)| i);
+    match comment {
+        Some(comment) => &text[..comment],
+        None => text
+    }
+}
+
+pub struct LineReader<R> {
+    reader: R,
+    physical: String,
+    number: usize
+}
+
+impl<R: BufRead> LineReader<R> {
+
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            physical: String::new(),
+            number: 0
+        }
+    }
+
+    // reads the next logical line into `text`. lines ending in `\` continue on the next one,
+    // line endings may be LF or CRLF and `#` starts a comment when it begins a token.
+    // returns the number of the first physical line read, or None at the end of the file
+    pub fn read_line(&mut self, text: &mut String) -> io::Result<Option<usize>> {
+
+        text.clear();
+        let mut first = None;
+        loop {
+            self.physical.clear();
+            if self.reader.read_line(&mut self.physical)? == 0 {
+                break;
+            }
+            self.number += 1;
+            first.get_or_insert(self.number);
+
+            let physical = self.physical.trim_end_matches(['\n', '\r']);
+            match physical.strip_suffix('\\') {
+                Some(continued) => {
+                    text.push_str(continued);
+                    text.push(' ');
+                }
+                None => {
+                    text.push_str(physical);
+                    break;
+                }
+            }
+        }
+
+        let len = strip_comment(text).len();
+        text.truncate(len);
+        Ok(first)
+    }
+}
+
+// the logical lines of text already in memory, read the same way as LineReader does. lines
+// are borrowed from the text unless they have to be joined with the next one
+pub struct Lines<'a> {
+    text: &'a str,
+    number: usize
+}
+
+impl<'a> Lines<'a> {
+
+    // `first_number` is the number of the first line of `text` within its file
+    pub fn new(text: &'a str, first_number: usize) -> Self {
+        Self {
+            text,
+            number: first_number - 1
+        }
+    }
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = (usize, Cow<'a, str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+
+        if self.text.is_empty() {
+            return None;
+        }
+        let first = self.number + 1;
+        let mut joined : Option<String> = None;
+        while !self.text.is_empty() {
+            let end = self.text.find('\n').map_or(self.text.len(), |i| i + 1);
+            let (physical, rest) = self.text.split_at(end);
+            self.text = rest;
+            self.number += 1;
+
+            let physical = physical.trim_end_matches(['\n', '\r']);
+            match physical.strip_suffix('\\') {
+                Some(continued) => {
+                    let joined = joined.get_or_insert_with(String::new);
+                    joined.push_str(continued);
+                    joined.push(' ');
+                }
+                None => {
+                    let line = match joined {
+                        Some(mut joined) => {
+                            joined.push_str(physical);
+                            let len = strip_comment(&joined).len();
+                            joined.truncate(len);
+                            Cow::Owned(joined)
+                        }
+                        None => Cow::Borrowed(strip_comment(physical))
+                    };
+                    return Some((first, line));
+                }
+            }
+        }
+        // the last line was continued, but the file ended
+        let mut joined = joined.unwrap_or_default();
+        let len = strip_comment(&joined).len();
+        joined.truncate(len);
+        Some((first, Cow::Owned(joined)))
+    }
+}
+
+// splits text into chunks of about `size` bytes that can be read on their own, each one ends
+// with a line break that doesn't continue the line
+pub fn split_chunks(bytes: &[u8], size: usize) -> Vec<&[u8]> {
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < bytes.len() {
+        let mut search = (start + size.max(1)).min(bytes.len()) - 1;
+        let end = loop {
+            let Some(newline) = bytes[search..].iter().position(|&b| b == b'\n').map(|i| search + i) else {
+                break bytes.len();
+            };
+            let mut line_end = newline;
+            while line_end > start && bytes[line_end - 1] == b'\r' {
+                line_end -= 1;
+            }
+            if line_end > start && bytes[line_end - 1] == b'\\' {
+                search = newline + 1;
+            } else {
+                break newline + 1;
+            }
+        };
+        chunks.push(&bytes[start..end]);
+        start = end;
+    }
+    chunks
+}
+
+// parses between `min` and N numbers, leaving the missing ones at zero
+pub fn parse_floats<const N: usize>(line: &Line, rest: &str, min: usize, expected: &'static str) -> Result<[f32; N], ObjError> {
+
+    let mut values = [0.0; N];
+    let mut count = 0;
+    for token in rest.split_whitespace() {
+        if count == N {
+            return Err(ObjError::WrongComponentCount { location: line.locate(rest), expected });
+        }
+        values[count] = token.parse::<f32>().map_err(|_| ObjError::BadNumber(line.locate(token)))?;
+        count += 1;
+    }
+    if count < min {
+        return Err(ObjError::WrongComponentCount { location: line.locate(rest), expected });
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (first physical line, keyword, rest) of every logical line that has something on it
+    fn split(source: &str) -> Vec<(usize, String, String)> {
+
+        let mut reader = LineReader::new(source.as_bytes());
+        let mut text = String::new();
+        let mut lines = Vec::new();
+        while let Some(number) = reader.read_line(&mut text).unwrap() {
+            let line = Line { file: "test.obj", number, text: &text };
+            if let Some((keyword, rest)) = line.split_keyword() {
+                lines.push((number, keyword.to_string(), rest.to_string()));
+            }
+        }
+        lines
+    }
+
+    fn line(number: usize, keyword: &str, rest: &str) -> (usize, String, String) {
+        (number, keyword.to_string(), rest.to_string())
+    }
+
+    #[test]
+    fn tabs_separate_tokens() {
+        assert_eq!(split("v\t1\t2\t3\n"), vec![line(1, "v", "1\t2\t3")]);
+    }
+
+    #[test]
+    fn repeated_and_leading_whitespace_is_ignored() {
+        assert_eq!(split("   v  1   2 \t 3  \n"), vec![line(1, "v", "1   2 \t 3")]);
+    }
+
+    #[test]
+    fn crlf_line_endings_are_stripped() {
+        assert_eq!(split("v 1 2 3\r\nf 1 2 3\r\n"), vec![line(1, "v", "1 2 3"), line(2, "f", "1 2 3")]);
+    }
+
+    #[test]
+    fn last_line_without_line_ending() {
+        assert_eq!(split("v 1 2 3\nf 1 2 3"), vec![line(1, "v", "1 2 3"), line(2, "f", "1 2 3")]);
+    }
+
+    #[test]
+    fn comments_are_removed() {
+        assert_eq!(split("# header\n  # indented\nv 1 2 3 # inline\nusemtl mat#1\n"), vec![line(3, "v", "1 2 3"), line(4, "usemtl", "mat#1")]);
+    }
+
+    #[test]
+    fn backslash_continues_on_next_line() {
+        assert_eq!(split("f 1 2 \\\n  3 4\\\r\n 5\nv 1 2 3\n"), vec![line(1, "f", "1 2    3 4  5"), line(4, "v", "1 2 3")]);
+    }
+
+    #[test]
+    fn continuation_at_end_of_file() {
+        assert_eq!(split("v 1 2 \\"), vec![line(1, "v", "1 2")]);
+    }
+
+    #[test]
+    fn lines_in_memory_match_line_reader() {
+
+        let source = "# header\r\nv\t1 2 3 # inline\r\nf 1 2 \\\r\n  3\n\nusemtl mat#1\nv 1 2 \\";
+        let borrowed = Lines::new(source, 1)
+            .map(|(number, text)| (number, text.into_owned()))
+            .collect::<Vec<_>>();
+        let mut reader = LineReader::new(source.as_bytes());
+        let mut text = String::new();
+        let mut read = Vec::new();
+        while let Some(number) = reader.read_line(&mut text).unwrap() {
+            read.push((number, text.clone()));
+        }
+        assert_eq!(borrowed, read);
+    }
+
+    #[test]
+    fn chunks_end_at_line_breaks_outside_continuations() {
+
+        let source = b"v 1 2 3\nf 1 \\\r\n2 3\nv 4 5 6\n";
+        for size in 1..source.len() + 2 {
+            let chunks = split_chunks(source, size);
+            assert_eq!(chunks.concat(), source.to_vec());
+            for chunk in &chunks {
+                assert!(chunk.ends_with(b"\n"));
+                assert!(!chunk.ends_with(b"\\\r\n"));
+            }
+        }
+        assert_eq!(split_chunks(b"v 1 2 3", 2), vec![b"v 1 2 3"]);
+    }
+
+    #[test]
+    fn parse_floats_reports_column_of_bad_token() {
+
+        let line = Line { file: "test.obj", number: 7, text: "v\t1  x 3" };
+        let (_, rest) = line.split_keyword().unwrap();
+        match parse_floats::<3>(&line, rest, 3, "3") {
+            Err(ObjError::BadNumber(location)) => assert_eq!((location.line, location.column, location.text.as_str()), (7, 6, "x")),
+            Err(other) => panic!("unexpected error {}", other),
+            Ok(_) => panic!("expected a bad number")
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use cgmath::InnerSpace;
+
+// splits a polygon into triangles, returned as indices into `polygon` with the original winding kept.
+// convex polygons are fanned, concave ones go through ear clipping, and non-planar ones
+// are projected onto their best fitting plane before either of those
+pub fn triangulate(polygon: &[[f32; 3]]) -> Vec<[usize; 3]> {
+
+    let n = polygon.len();
+    if n < 3 {
+        return Vec::new();
+    }
+    if n == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    let normal = newell_normal(polygon);
+    if normal.magnitude2() <= f32::EPSILON {
+        // degenerate polygon, there is no plane to project onto
+        return fan(n);
+    }
+    let projected = project(polygon, normal.normalize());
+
+    if is_convex(&projected) {
+        fan(n)
+    } else {
+        ear_clip(&projected)
+    }
+}
+
+fn fan(n: usize) -> Vec<[usize; 3]> {
+    (1..n - 1).map(|i| [0, i, i + 1]).collect()
+}
+
+// normal of the plane that best fits the polygon, also valid for non-planar and concave ones
+fn newell_normal(polygon: &[[f32; 3]]) -> cgmath::Vector3<f32> {
+
+    let mut normal = cgmath::Vector3::new(0.0, 0.0, 0.0);
+    for (i, current) in polygon.iter().enumerate() {
+        let next = polygon[(i + 1) % polygon.len()];
+        normal.x += (current[1] - next[1]) * (current[2] + next[2]);
+        normal.y += (current[2] - next[2]) * (current[0] + next[0]);
+        normal.z += (current[0] - next[0]) * (current[1] + next[1]);
+    }
+    normal
+}
+
+// coordinates on the plane perpendicular to `normal`, chosen so the polygon winds counter-clockwise
+fn project(polygon: &[[f32; 3]], normal: cgmath::Vector3<f32>) -> Vec<[f32; 2]> {
+
+    let helper = if normal.x.abs() < 0.9 {
+        cgmath::Vector3::unit_x()
+    } else {
+        cgmath::Vector3::unit_y()
+    };
+    let u = helper.cross(normal).normalize();
+    let v = normal.cross(u);
+    polygon.iter().map(|p| {
+        let p = cgmath::Vector3::from(*p);
+        [p.dot(u), p.dot(v)]
+    }).collect()
+}
+
+fn cross(o: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+fn is_convex(polygon: &[[f32; 2]]) -> bool {
+
+    let n = polygon.len();
+    (0..n).all(|i| cross(polygon[i], polygon[(i + 1) % n], polygon[(i + 2) % n]) >= 0.0)
+}
+
+fn contains(a: [f32; 2], b: [f32; 2], c: [f32; 2], p: [f32; 2]) -> bool {
+    cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0
+}
+
+fn ear_clip(polygon: &[[f32; 2]]) -> Vec<[usize; 3]> {
+
+    let mut remaining : Vec<usize> = (0..polygon.len()).collect();
+    let mut triangles = Vec::with_capacity(polygon.len() - 2);
+
+    while remaining.len() > 3 {
+
+        let n = remaining.len();
+        let is_ear = |i: usize| {
+            let (prev, curr, next) = (remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]);
+            let (a, b, c) = (polygon[prev], polygon[curr], polygon[next]);
+            // reflex corners can't be ears
+            if cross(a, b, c) <= 0.0 {
+                return false;
+            }
+            remaining.iter()
+                .filter(|&&other| other != prev && other != curr && other != next)
+                .all(|&other| !contains(a, b, c, polygon[other]))
+        };
+
+        // self-intersecting input, or corners lying on the edges of others, may leave no ear.
+        // a convex corner is clipped anyway so the winding is kept, even though its triangle
+        // overlaps the rest of the polygon, and the first corner when none is convex
+        let ear = (0..n).find(|&i| is_ear(i)).unwrap_or_else(|| {
+            log::debug!("no ear left in a polygon of {} corners, clipping one that may overlap", n);
+            (0..n).find(|&i| cross(polygon[remaining[(i + n - 1) % n]], polygon[remaining[i]], polygon[remaining[(i + 1) % n]]) > 0.0).unwrap_or(0)
+        });
+        triangles.push([remaining[(ear + n - 1) % n], remaining[ear], remaining[(ear + 1) % n]]);
+        remaining.remove(ear);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // twice the signed area of each triangle, on the plane the polygon is projected onto
+    fn areas(polygon: &[[f32; 3]], triangles: &[[usize; 3]]) -> Vec<f32> {
+
+        let projected = project(polygon, newell_normal(polygon).normalize());
+        triangles.iter().map(|&[a, b, c]| cross(projected[a], projected[b], projected[c])).collect()
+    }
+
+    #[test]
+    fn convex_polygons_are_fanned() {
+
+        let square = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+        assert_eq!(triangulate(&square), vec![[0, 1, 2], [0, 2, 3]]);
+        // twice the area, pointing out of the counter-clockwise side
+        assert_eq!(newell_normal(&square), cgmath::Vector3::new(0.0, 0.0, 2.0));
+    }
+
+    #[test]
+    fn concave_polygons_are_clipped_without_leaving_them() {
+
+        // an L of three unit squares, its reflex corner at (1, 1)
+        let l = [[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [2.0, 1.0, 0.0], [1.0, 1.0, 0.0], [1.0, 2.0, 0.0], [0.0, 2.0, 0.0]];
+        let triangles = triangulate(&l);
+        assert_eq!(triangles.len(), 4);
+        let areas = areas(&l, &triangles);
+        assert!(areas.iter().all(|&area| area > 0.0), "{:?}", areas);
+        assert!((areas.iter().sum::<f32>() - 6.0).abs() < 1e-5);
+        // a fan from the first corner would cross the notch
+        assert_ne!(triangles, fan(l.len()));
+    }
+
+    #[test]
+    fn non_planar_polygons_are_projected_onto_their_best_plane() {
+
+        // a quad with one corner lifted, tilted away from every axis
+        let quad = [[0.0, 0.0, 0.0], [1.0, 0.0, 1.0], [1.0, 1.0, 2.3], [0.0, 1.0, 1.0]];
+        let normal = newell_normal(&quad).normalize();
+        let flat_normal = cgmath::Vector3::new(-1.0, -1.0, 1.0).normalize();
+        assert!(normal.dot(flat_normal) > 0.99);
+        let triangles = triangulate(&quad);
+        assert_eq!(triangles.len(), 2);
+        assert!(areas(&quad, &triangles).iter().all(|&area| area > 0.0));
+    }
+
+    #[test]
+    fn degenerate_polygons_still_give_every_triangle() {
+
+        let collinear = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [2.0, 0.0, 0.0], [3.0, 0.0, 0.0]];
+        assert_eq!(triangulate(&collinear), fan(4));
+        assert!(triangulate(&collinear[..2]).is_empty());
+
+        // a bow tie crosses itself, so some triangle overlaps, but none is lost
+        let bow_tie = [[0.0, 0.0], [2.0, 2.0], [2.0, 0.0], [0.0, 2.0], [-1.0, 1.0]];
+        let triangles = ear_clip(&bow_tie);
+        assert_eq!(triangles.len(), 3);
+        let mut corners = triangles.concat();
+        corners.sort_unstable();
+        corners.dedup();
+        assert_eq!(corners, vec![0, 1, 2, 3, 4]);
+    }
+}
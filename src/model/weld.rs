@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use super::MeshData;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WeldReport {
+    // vertices folded into an equal one
+    pub merged: usize,
+    // vertices no index referred to, and positions of an OBJ file no element referred to
+    pub unreferenced: usize
+}
+
+impl std::ops::AddAssign for WeldReport {
+
+    fn add_assign(&mut self, other: Self) {
+        self.merged += other.merged;
+        self.unreferenced += other.unreferenced;
+    }
+}
+
+// all attributes of a vertex, compared together
+fn attributes(mesh: &MeshData, vertex: usize) -> [f32; 15] {
+
+    let [px, py, pz] = mesh.positions[vertex];
+    let [nx, ny, nz] = mesh.normals[vertex];
+    let [u, v] = mesh.texcoords.get(vertex).copied().unwrap_or([0.0; 2]);
+    let [r, g, b] = mesh.colors.get(vertex).copied().unwrap_or([1.0; 3]);
+    let [tx, ty, tz, tw] = mesh.tangents.get(vertex).copied().unwrap_or([0.0; 4]);
+    // adding zero turns -0.0 into 0.0, so both hash the same
+    [px, py, pz, nx, ny, nz, u, v, r, g, b, tx, ty, tz, tw].map(|value| value + 0.0)
+}
+
+fn cell(position: [f32; 3], epsilon: f32) -> [i64; 3] {
+    position.map(|value| (value / epsilon).floor() as i64)
+}
+
+// merges vertices whose attributes all differ by at most `epsilon` (or are identical when it is
+// zero), drops the ones no index refers to and rewrites the index buffer to match
+pub fn weld(mesh: &mut MeshData, epsilon: f32) -> WeldReport {
+
+    let vertex_count = mesh.positions.len();
+    let mut referenced = vec![false; vertex_count];
+    for &index in mesh.indices.iter().chain(&mesh.line_indices).chain(&mesh.point_indices) {
+        referenced[index as usize] = true;
+    }
+
+    let mut remap = vec![u32::MAX; vertex_count];
+    let mut kept : Vec<usize> = Vec::new();
+    if epsilon > 0.0 {
+        // candidates are only searched in the grid cells around each position
+        let mut grid : HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+        for vertex in (0..vertex_count).filter(|&v| referenced[v]) {
+            let values = attributes(mesh, vertex);
+            let [cx, cy, cz] = cell(mesh.positions[vertex], epsilon);
+            let mut found = None;
+            'search: for x in cx - 1..=cx + 1 {
+                for y in cy - 1..=cy + 1 {
+                    for z in cz - 1..=cz + 1 {
+                        for &candidate in grid.get(&[x, y, z]).into_iter().flatten() {
+                            let other = attributes(mesh, kept[candidate]);
+                            let close = |a: f32, b: f32| (a - b).abs() <= epsilon;
+                            let scalars_close = mesh.scalars.iter().all(|scalar| close(scalar.values[vertex], scalar.values[kept[candidate]]));
+                            if values.iter().zip(other.iter()).all(|(a, b)| close(*a, *b)) && scalars_close {
+                                found = Some(candidate);
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+            remap[vertex] = match found {
+                Some(candidate) => candidate as u32,
+                None => {
+                    grid.entry([cx, cy, cz]).or_default().push(kept.len());
+                    kept.push(vertex);
+                    (kept.len() - 1) as u32
+                }
+            };
+        }
+    } else {
+        let mut unique : HashMap<([u32; 15], Vec<u32>), u32> = HashMap::new();
+        for vertex in (0..vertex_count).filter(|&v| referenced[v]) {
+            let scalars = mesh.scalars.iter().map(|scalar| (scalar.values[vertex] + 0.0).to_bits()).collect();
+            let key = (attributes(mesh, vertex).map(f32::to_bits), scalars);
+            remap[vertex] = *unique.entry(key).or_insert_with(|| {
+                kept.push(vertex);
+                (kept.len() - 1) as u32
+            });
+        }
+    }
+
+    let referenced_count = referenced.iter().filter(|&&r| r).count();
+    for index in mesh.indices.iter_mut().chain(&mut mesh.line_indices).chain(&mut mesh.point_indices) {
+        *index = remap[*index as usize];
+    }
+    mesh.positions = kept.iter().map(|&v| mesh.positions[v]).collect();
+    mesh.normals = kept.iter().map(|&v| mesh.normals[v]).collect();
+    if !mesh.texcoords.is_empty() {
+        mesh.texcoords = kept.iter().map(|&v| mesh.texcoords[v]).collect();
+    }
+    if !mesh.colors.is_empty() {
+        mesh.colors = kept.iter().map(|&v| mesh.colors[v]).collect();
+    }
+    if !mesh.tangents.is_empty() {
+        mesh.tangents = kept.iter().map(|&v| mesh.tangents[v]).collect();
+    }
+    for scalar in &mut mesh.scalars {
+        scalar.values = kept.iter().map(|&v| scalar.values[v]).collect();
+    }
+
+    WeldReport {
+        merged: referenced_count - kept.len(),
+        unreferenced: vertex_count - referenced_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{LoadOptions, ParseMode};
+
+    // two triangles along the x axis, the corners of the second shifted by `gap`, and an
+    // unreferenced vertex at the end
+    fn strip(gap: f32) -> MeshData {
+
+        let positions = vec![
+            [0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0],
+            [1.0 + gap, 0.0, 0.0], [2.0, 0.0, 0.0], [1.0 + gap, 1.0, 0.0],
+            [5.0, 5.0, 5.0]
+        ];
+        MeshData {
+            normals: vec![[0.0, 0.0, 1.0]; positions.len()],
+            positions,
+            indices: vec![0, 1, 2, 3, 4, 5],
+            ..MeshData::default()
+        }
+    }
+
+    #[test]
+    fn corners_within_epsilon_are_merged_and_others_kept_apart() {
+
+        let mut exact = strip(0.0);
+        assert_eq!(weld(&mut exact, 0.0), WeldReport { merged: 2, unreferenced: 1 });
+        assert_eq!(exact.positions.len(), 4);
+        assert_eq!(exact.indices, vec![0, 1, 2, 1, 3, 2]);
+
+        let mut close = strip(0.001);
+        assert_eq!(weld(&mut close, 0.01), WeldReport { merged: 2, unreferenced: 1 });
+        assert_eq!(close.indices, vec![0, 1, 2, 1, 3, 2]);
+
+        // beyond epsilon, and any difference at all without one
+        let mut apart = strip(0.1);
+        assert_eq!(weld(&mut apart, 0.01), WeldReport { merged: 0, unreferenced: 1 });
+        assert_eq!(apart.positions.len(), 6);
+        assert_eq!(weld(&mut strip(0.001), 0.0).merged, 0);
+    }
+
+    #[test]
+    fn corners_differing_in_another_attribute_are_kept_apart() {
+
+        let mut mesh = strip(0.0);
+        mesh.normals[3] = [0.0, 1.0, 0.0];
+        assert_eq!(weld(&mut mesh, 0.01).merged, 1);
+    }
+
+    #[test]
+    fn obj_positions_no_element_uses_are_reported() {
+
+        let options = LoadOptions { mode: ParseMode::Strict, ..LoadOptions::default() };
+        let source = b"v 0 0 0\nv 1 0 0\nv 0 1 0\nv 9 9 9\nv 8 8 8\nf 1 2 3\n";
+        let (_, report) = MeshData::from_obj(&source[..], "test.obj", &options).unwrap();
+        assert_eq!(report.weld, WeldReport { merged: 0, unreferenced: 2 });
+    }
+}
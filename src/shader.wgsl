@@ -9,6 +9,10 @@ struct CameraUniform {
 struct VertexInput {
     [[location(0)]] position: vec3<f32>;
     [[location(1)]] normal: vec3<f32>;
+    [[location(9)]] color: vec3<f32>;
+    [[location(10)]] tex_coords: vec2<f32>;
+    // w is the sign of the bitangent
+    [[location(11)]] tangent: vec4<f32>;
 };
 
 struct InstanceInput {
@@ -34,11 +38,39 @@ struct LightUniform {
 [[group(1), binding(0)]]
 var<uniform> light: LightUniform;
 
+[[block]]
+struct MaterialUniform {
+    ambient: vec3<f32>;
+    shininess: f32;
+    diffuse: vec3<f32>;
+    dissolve: f32;
+    specular: vec3<f32>;
+    illumination: u32;
+    // non-zero to use the vertex colours in place of the ambient and diffuse colours
+    vertex_colors: u32;
+    // non-zero to perturb the normals with the normal map
+    normal_map: u32;
+};
+
+[[group(2), binding(0)]]
+var<uniform> material: MaterialUniform;
+[[group(2), binding(1)]]
+var diffuse_map: texture_2d<f32>;
+[[group(2), binding(2)]]
+var diffuse_sampler: sampler;
+[[group(2), binding(3)]]
+var normal_map: texture_2d<f32>;
+[[group(2), binding(4)]]
+var normal_sampler: sampler;
+
 struct VertexOutput {
     [[builtin(position)]] clip_position: vec4<f32>;
     [[location(0)]] color: vec3<f32>;
     [[location(1)]] world_normal: vec3<f32>;
     [[location(2)]] world_position: vec3<f32>;
+    [[location(3)]] tex_coords: vec2<f32>;
+    [[location(4)]] world_tangent: vec3<f32>;
+    [[location(5)]] tangent_sign: f32;
 };
 
 [[stage(vertex)]]
@@ -59,7 +91,12 @@ fn vs_main(
     );
     var out: VertexOutput;
 
+    out.color = model.color;
+    out.tex_coords = model.tex_coords;
     out.world_normal = normal_matrix * model.normal;
+    // tangents lie in the surface, so they follow the model matrix rather than the normal matrix
+    out.world_tangent = (model_matrix * vec4<f32>(model.tangent.xyz, 0.0)).xyz;
+    out.tangent_sign = model.tangent.w;
     var world_position: vec4<f32> = model_matrix * vec4<f32>(model.position, 1.0);
     out.world_position = world_position.xyz;
     out.clip_position = camera.view_proj * world_position;
@@ -70,20 +107,59 @@ fn vs_main(
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
 
-    let object_color: vec4<f32> = vec4<f32>(0.3, 0.2, 0.5, 0.1);
+    var ambient: vec3<f32> = material.ambient;
+    var diffuse: vec3<f32> = material.diffuse;
+    if (material.vertex_colors != 0u) {
+        ambient = in.color;
+        diffuse = in.color;
+    }
+    // materials without a diffuse map sample a white texture
+    let texel = textureSample(diffuse_map, diffuse_sampler, in.tex_coords).rgb;
+    ambient = ambient * texel;
+    diffuse = diffuse * texel;
+
+    var normal: vec3<f32> = normalize(in.world_normal);
+    if (material.normal_map != 0u) {
+        // interpolation leaves the tangent slightly off perpendicular to the normal
+        let tangent = normalize(in.world_tangent - dot(in.world_tangent, normal) * normal);
+        let bitangent = cross(normal, tangent) * in.tangent_sign;
+        let texel_normal = textureSample(normal_map, normal_sampler, in.tex_coords).xyz * 2.0 - 1.0;
+        normal = normalize(mat3x3<f32>(tangent, bitangent, normal) * texel_normal);
+    }
+
+    // illumination model 0 is a constant color
+    if (material.illumination == 0u) {
+        return vec4<f32>(diffuse, material.dissolve);
+    }
+
     let ambient_strenght = 0.1;
-    let ambient_color = light.color * ambient_strenght;
+    let ambient_color = light.color * ambient_strenght * ambient;
 
     let light_dir = normalize(light.position - in.world_position);
 
-    let diffuse_strength = max(dot(in.world_normal, light_dir), 0.0);
-    let diffuse_color = light.color * diffuse_strength;
+    let diffuse_strength = max(dot(normal, light_dir), 0.0);
+    let diffuse_color = light.color * diffuse_strength * diffuse;
 
-    let view_dir = normalize(camera.view_pos.xyz - in.world_position);
-    let half_dir = normalize(view_dir + light_dir);
-    let specular_strength = pow(max(dot(in.world_normal, half_dir), 0.0), 32.0);
-    let specular_color = specular_strength * light.color;
+    // illumination model 1 has no highlights
+    var specular_color: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+    if (material.illumination != 1u) {
+        let view_dir = normalize(camera.view_pos.xyz - in.world_position);
+        let half_dir = normalize(view_dir + light_dir);
+        let specular_strength = pow(max(dot(normal, half_dir), 0.0), material.shininess);
+        specular_color = specular_strength * light.color * material.specular;
+    }
 
-    let result = (ambient_color + diffuse_color + specular_color) * object_color.xyz;
-    return vec4<f32>(result, object_color.a);
+    let result = ambient_color + diffuse_color + specular_color;
+    return vec4<f32>(result, material.dissolve);
+}
+
+// lines and points have no surface to light
+[[stage(fragment)]]
+fn fs_unlit(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_highlight(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(1.0, 0.8, 0.0, 1.0);
 }
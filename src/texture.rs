@@ -1,5 +1,45 @@
+use std::{num::NonZeroU32, path::Path};
+
+// how an image texture is sampled
+#[derive(Debug, Clone, Copy)]
+pub struct SamplerOptions {
+    pub address_mode: wgpu::AddressMode,
+    pub filter: wgpu::FilterMode
+}
+
+impl Default for SamplerOptions {
+
+    // OBJ texture coordinates commonly go past 0 and 1 to tile the image
+    fn default() -> Self {
+        Self {
+            address_mode: wgpu::AddressMode::Repeat,
+            filter: wgpu::FilterMode::Linear
+        }
+    }
+}
+
+// how the texels of an image are to be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    // colours, decoded to linear when sampled
+    Srgb,
+    // data such as normal maps, sampled as stored
+    Linear
+}
+
+impl ColorSpace {
+
+    fn format(self) -> wgpu::TextureFormat {
+        match self {
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm
+        }
+    }
+}
+
 pub struct Texture {
 
+    #[allow(dead_code)]
     texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     sampler: wgpu::Sampler
@@ -43,4 +83,129 @@ impl Texture {
         );
         Self { texture, view, sampler }
     }
+
+    // decodes a PNG, JPEG or TGA file
+    pub fn from_path<P: AsRef<Path>>(device: &wgpu::Device, queue: &wgpu::Queue, path: P, color_space: ColorSpace, sampler: &SamplerOptions) -> Result<Self, image::ImageError> {
+
+        let path = path.as_ref();
+        let image = image::open(path)?;
+        Ok(Self::from_image(device, queue, &image, &path.to_string_lossy(), color_space, sampler))
+    }
+
+    // decodes an image already in memory, its format is guessed from its contents
+    pub fn from_bytes(device: &wgpu::Device, queue: &wgpu::Queue, bytes: &[u8], label: &str, color_space: ColorSpace, sampler: &SamplerOptions) -> Result<Self, image::ImageError> {
+
+        let image = image::load_from_memory(bytes)?;
+        Ok(Self::from_image(device, queue, &image, label, color_space, sampler))
+    }
+
+    // colour textures are authored as sRGB, normal maps as linear data
+    pub fn from_image(device: &wgpu::Device, queue: &wgpu::Queue, image: &image::DynamicImage, label: &str, color_space: ColorSpace, sampler: &SamplerOptions) -> Self {
+
+        let rgba = Self::rgba(image, device.limits().max_texture_dimension_2d, label);
+        let (width, height) = rgba.dimensions();
+        Self::from_rgba(device, queue, &rgba, width, height, label, color_space, sampler)
+    }
+
+    // the texels of an image as RGBA, shrunk to fit when it is larger than the device allows
+    fn rgba(image: &image::DynamicImage, max_dimension: u32, label: &str) -> image::RgbaImage {
+
+        if image.width() <= max_dimension && image.height() <= max_dimension {
+            return image.to_rgba8();
+        }
+        log::warn!("{} is {}x{}, larger than the {} texels a texture can have, it is scaled down", label, image.width(), image.height(), max_dimension);
+        image.resize(max_dimension, max_dimension, image::imageops::FilterType::Triangle).to_rgba8()
+    }
+
+    // a single white texel, for materials without a texture to sample
+    pub fn white(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self::from_rgba(device, queue, &[255; 4], 1, 1, "white", ColorSpace::Srgb, &SamplerOptions::default())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn from_rgba(device: &wgpu::Device, queue: &wgpu::Queue, rgba: &[u8], width: u32, height: u32, label: &str, color_space: ColorSpace, sampler: &SamplerOptions) -> Self {
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: color_space.format(),
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST
+        });
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All
+            },
+            rgba,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(4 * width),
+                rows_per_image: NonZeroU32::new(height)
+            },
+            size
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: sampler.address_mode,
+                address_mode_v: sampler.address_mode,
+                address_mode_w: sampler.address_mode,
+                mag_filter: sampler.filter,
+                min_filter: sampler.filter,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }
+        );
+        Self { texture, view, sampler }
+    }
+
+    pub fn get_sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn png(image: image::DynamicImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image.write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn images_without_alpha_become_opaque_rgba() {
+
+        let rgb = image::RgbImage::from_fn(2, 1, |x, _| image::Rgb([10 * x as u8, 20, 30]));
+        let image = image::load_from_memory(&png(rgb.into())).unwrap();
+        let rgba = Texture::rgba(&image, 8192, "rgb.png");
+        assert_eq!(rgba.dimensions(), (2, 1));
+        assert_eq!(rgba.into_raw(), vec![0, 20, 30, 255, 10, 20, 30, 255]);
+
+        let gray = image::GrayImage::from_pixel(1, 1, image::Luma([7]));
+        let image = image::load_from_memory(&png(gray.into())).unwrap();
+        assert_eq!(Texture::rgba(&image, 8192, "gray.png").into_raw(), vec![7, 7, 7, 255]);
+    }
+
+    #[test]
+    fn images_larger_than_the_device_allows_are_scaled_down() {
+
+        let image = image::load_from_memory(&png(image::RgbaImage::new(64, 16).into())).unwrap();
+        assert_eq!(Texture::rgba(&image, 64, "wide.png").dimensions(), (64, 16));
+        // the aspect ratio is kept
+        assert_eq!(Texture::rgba(&image, 32, "wide.png").dimensions(), (32, 8));
+    }
 }